@@ -0,0 +1,26 @@
+-- names were not unique before; later duplicates get a numeric suffix, or their id when a user
+-- already has the suffixed name
+UPDATE `users` AS `u`
+INNER JOIN (
+    SELECT
+        `d`.`id`,
+        CASE
+            WHEN EXISTS (SELECT 1 FROM `users` AS `t` WHERE `t`.`name` = `d`.`candidate`)
+            THEN CONCAT(`d`.`name`, '-', LOWER(HEX(`d`.`id`)))
+            ELSE `d`.`candidate`
+        END AS `new_name`
+    FROM (
+        SELECT
+            `id`,
+            `name`,
+            ROW_NUMBER() OVER (PARTITION BY `name` ORDER BY `id`) AS `n`,
+            CONCAT(`name`, '-', ROW_NUMBER() OVER (PARTITION BY `name` ORDER BY `id`) - 1)
+                AS `candidate`
+        FROM `users`
+    ) AS `d`
+    WHERE `d`.`n` > 1
+) AS `r` ON `r`.`id` = `u`.`id`
+SET `u`.`name` = `r`.`new_name`;
+
+ALTER TABLE `users`
+    ADD UNIQUE INDEX `users_name` (`name`);
@@ -0,0 +1,10 @@
+-- The old `name` becomes the immutable handle; the display name starts out equal to it.
+ALTER TABLE `users`
+    DROP INDEX `users_name`,
+    CHANGE COLUMN `name` `handle` VARCHAR(255) NOT NULL,
+    ADD UNIQUE INDEX `users_handle` (`handle`),
+    ADD COLUMN `display_name` VARCHAR(255) NOT NULL DEFAULT '' AFTER `handle`,
+    ADD COLUMN `bio` VARCHAR(1000) NOT NULL DEFAULT '' AFTER `display_name`,
+    ADD COLUMN `avatar_url` VARCHAR(2048) NULL AFTER `bio`;
+
+UPDATE `users` SET `display_name` = `handle`;
@@ -0,0 +1,6 @@
+-- bumped on every write; updates and deletes may require the version the client last saw
+ALTER TABLE `users`
+    ADD COLUMN `version` BIGINT UNSIGNED NOT NULL DEFAULT 1 AFTER `avatar_url`;
+
+ALTER TABLE `messages`
+    ADD COLUMN `version` BIGINT UNSIGNED NOT NULL DEFAULT 1;
@@ -0,0 +1,4 @@
+-- deleted users keep their row, and handle, until they are purged after the retention period
+ALTER TABLE `users`
+    ADD COLUMN `deleted_at` TIMESTAMP NULL DEFAULT NULL AFTER `version`,
+    ADD INDEX `users_deleted_at` (`deleted_at`);
@@ -0,0 +1,3 @@
+-- messages are looked up by author when their author is deleted
+ALTER TABLE `messages`
+    ADD INDEX `messages_created_by` (`created_by`);
@@ -0,0 +1,13 @@
+-- group channels created before roles existed get their longest standing member as owner
+UPDATE `channel_members` AS `m`
+INNER JOIN (
+    SELECT
+        `channel_id`,
+        `user_id`,
+        ROW_NUMBER() OVER (PARTITION BY `channel_id` ORDER BY `joined_at`, `user_id`) AS `n`,
+        SUM(`role` = 'owner') OVER (PARTITION BY `channel_id`) AS `owners`
+    FROM `channel_members`
+) AS `r` ON `r`.`channel_id` = `m`.`channel_id` AND `r`.`user_id` = `m`.`user_id`
+INNER JOIN `channels` AS `c` ON `c`.`id` = `m`.`channel_id`
+SET `m`.`role` = 'owner'
+WHERE `r`.`n` = 1 AND `r`.`owners` = 0 AND `c`.`kind` = 'group';
@@ -0,0 +1,10 @@
+-- handles, display names, channel names and messages take any Unicode text, emoji included,
+-- and searching compares with a utf8mb4 collation, which only applies to utf8mb4 columns
+ALTER TABLE `users` CONVERT TO CHARACTER SET utf8mb4;
+ALTER TABLE `messages` CONVERT TO CHARACTER SET utf8mb4;
+ALTER TABLE `channels` CONVERT TO CHARACTER SET utf8mb4;
+ALTER TABLE `channel_members` CONVERT TO CHARACTER SET utf8mb4;
+ALTER TABLE `direct_channels` CONVERT TO CHARACTER SET utf8mb4;
+ALTER TABLE `credentials` CONVERT TO CHARACTER SET utf8mb4;
+ALTER TABLE `sessions` CONVERT TO CHARACTER SET utf8mb4;
+ALTER TABLE `api_keys` CONVERT TO CHARACTER SET utf8mb4;
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS `messages` (
+    `id` BINARY(16) NOT NULL,
+    `text` TEXT NOT NULL,
+    `created_by` BINARY(16),
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `updated_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`)
+);
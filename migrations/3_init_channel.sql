@@ -0,0 +1,25 @@
+CREATE TABLE IF NOT EXISTS `channels` (
+    `id` BINARY(16) NOT NULL,
+    `name` VARCHAR(255) NOT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `updated_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    `archived_at` TIMESTAMP NULL DEFAULT NULL,
+    PRIMARY KEY (`id`)
+);
+
+-- messages posted before channels existed are moved to a `general` channel
+INSERT INTO `channels` (`id`, `name`, `created_at`, `updated_at`)
+SELECT UNHEX(REPLACE(UUID(), '-', '')), 'general', NOW(), NOW()
+FROM DUAL
+WHERE EXISTS (SELECT 1 FROM `messages`);
+
+ALTER TABLE `messages` ADD COLUMN `channel_id` BINARY(16) NULL AFTER `id`;
+
+UPDATE `messages`
+SET
+    `channel_id` = (SELECT `id` FROM `channels` WHERE `name` = 'general' LIMIT 1),
+    `updated_at` = `updated_at`;
+
+ALTER TABLE `messages`
+    MODIFY COLUMN `channel_id` BINARY(16) NOT NULL,
+    ADD INDEX `messages_channel_id` (`channel_id`, `id`);
@@ -0,0 +1,10 @@
+ALTER TABLE `channels`
+    ADD COLUMN `visibility` ENUM('public', 'private') NOT NULL DEFAULT 'public' AFTER `name`;
+
+CREATE TABLE IF NOT EXISTS `channel_members` (
+    `channel_id` BINARY(16) NOT NULL,
+    `user_id` BINARY(16) NOT NULL,
+    `joined_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (`channel_id`, `user_id`),
+    INDEX `channel_members_user_id` (`user_id`)
+);
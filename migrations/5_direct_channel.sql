@@ -0,0 +1,11 @@
+ALTER TABLE `channels`
+    ADD COLUMN `kind` ENUM('group', 'direct') NOT NULL DEFAULT 'group' AFTER `name`;
+
+-- `user_low` sorts before or equals `user_high` so each pair has a single row
+CREATE TABLE IF NOT EXISTS `direct_channels` (
+    `channel_id` BINARY(16) NOT NULL,
+    `user_low` BINARY(16) NOT NULL,
+    `user_high` BINARY(16) NOT NULL,
+    PRIMARY KEY (`channel_id`),
+    UNIQUE INDEX `direct_channels_users` (`user_low`, `user_high`)
+);
@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS `credentials` (
+    `user_id` BINARY(16) NOT NULL,
+    -- PHC string format, e.g. `$argon2id$v=19$...`
+    `password_hash` VARCHAR(255) NOT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `updated_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    PRIMARY KEY (`user_id`)
+);
+
+-- tokens themselves are never stored, only their SHA-256 digests
+CREATE TABLE IF NOT EXISTS `sessions` (
+    `id` BINARY(16) NOT NULL,
+    `user_id` BINARY(16) NOT NULL,
+    `access_token_hash` BINARY(32) NOT NULL,
+    `refresh_token_hash` BINARY(32) NOT NULL,
+    `access_token_expires_at` TIMESTAMP NOT NULL,
+    `refresh_token_expires_at` TIMESTAMP NOT NULL,
+    `revoked_at` TIMESTAMP NULL DEFAULT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`),
+    UNIQUE INDEX `sessions_access_token_hash` (`access_token_hash`),
+    UNIQUE INDEX `sessions_refresh_token_hash` (`refresh_token_hash`),
+    INDEX `sessions_user_id` (`user_id`)
+);
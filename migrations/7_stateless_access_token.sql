@@ -0,0 +1,5 @@
+-- access tokens are signed and verified without the DB; sessions only back refresh tokens
+ALTER TABLE `sessions`
+    DROP INDEX `sessions_access_token_hash`,
+    DROP COLUMN `access_token_hash`,
+    DROP COLUMN `access_token_expires_at`;
@@ -0,0 +1,14 @@
+-- like sessions, only the SHA-256 digest of the secret is stored
+CREATE TABLE IF NOT EXISTS `api_keys` (
+    `id` BINARY(16) NOT NULL,
+    `user_id` BINARY(16) NOT NULL,
+    `label` VARCHAR(255) NOT NULL,
+    `key_hash` BINARY(32) NOT NULL,
+    `scopes` SET('messages:read', 'messages:write', 'channels:read', 'channels:write', 'users:read') NOT NULL,
+    `last_used_at` TIMESTAMP NULL DEFAULT NULL,
+    `revoked_at` TIMESTAMP NULL DEFAULT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`),
+    UNIQUE INDEX `api_keys_key_hash` (`key_hash`),
+    INDEX `api_keys_user_id` (`user_id`)
+);
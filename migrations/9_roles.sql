@@ -0,0 +1,8 @@
+-- there is no API to grant admin; promote with
+-- `UPDATE users SET role = 'admin' WHERE id = ...`
+ALTER TABLE `users`
+    ADD COLUMN `role` ENUM('user', 'admin') NOT NULL DEFAULT 'user' AFTER `name`;
+
+-- owners of channels created before roles existed are backfilled in 15_backfill_channel_owner.sql
+ALTER TABLE `channel_members`
+    ADD COLUMN `role` ENUM('member', 'moderator', 'owner') NOT NULL DEFAULT 'member' AFTER `user_id`;
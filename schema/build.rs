@@ -2,6 +2,7 @@ use std::path::Path;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let proto_dir = Path::new("../proto").canonicalize()?;
+    println!("cargo:rerun-if-changed={}", proto_dir.display());
     tonic_prost_build::configure()
         .build_client(false)
         .build_server(true)
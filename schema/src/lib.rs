@@ -6,6 +6,18 @@ pub mod user {
     tonic::include_proto!("chatting.user");
 }
 
+pub mod auth {
+    tonic::include_proto!("chatting.auth");
+}
+
+pub mod channel {
+    tonic::include_proto!("chatting.channel");
+}
+
 pub mod message {
     tonic::include_proto!("chatting.message");
 }
+
+pub mod presence {
+    tonic::include_proto!("chatting.presence");
+}
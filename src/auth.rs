@@ -0,0 +1,298 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Failure,
+    prelude::Timestamp,
+    user::{User, UserHandle, UserId},
+};
+
+mod revocation;
+mod svc;
+mod token;
+
+pub use revocation::RevocationList;
+pub use svc::Impl as AuthServiceImpl;
+pub(crate) use svc::{delete_user_credentials, revoke_user_credentials};
+pub use token::{Keyring, SigningKey};
+
+/// A credential presented as `authorization: Bearer <token>`.
+#[derive(Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct BearerToken(pub String);
+
+impl std::fmt::Debug for BearerToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("BearerToken(..)")
+    }
+}
+
+/// Exchanged for a new [`Session`] once the bearer token expires.
+#[derive(Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct RefreshToken(pub String);
+
+impl std::fmt::Debug for RefreshToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RefreshToken(..)")
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Password(pub String);
+
+impl std::fmt::Debug for Password {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Password(..)")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Session {
+    pub user_id: UserId,
+    pub access_token: BearerToken,
+    pub access_token_expires_at: Timestamp,
+    pub refresh_token: RefreshToken,
+    pub refresh_token_expires_at: Timestamp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Registration {
+    pub user: User,
+    pub session: Session,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct ApiKeyId(pub uuid::Uuid);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct ApiKeyLabel(pub String);
+
+/// What an API key may be used for. Each scope covers a fixed set of RPCs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    MessagesRead,
+    MessagesWrite,
+    ChannelsRead,
+    ChannelsWrite,
+    UsersRead,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct ApiKey {
+    pub id: ApiKeyId,
+    pub user_id: UserId,
+    pub label: ApiKeyLabel,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_at: Timestamp,
+    pub last_used_at: Option<Timestamp>,
+    pub revoked_at: Option<Timestamp>,
+}
+
+impl ApiKey {
+    /// Names API keys in reject details.
+    pub const RESOURCE_TYPE: &str = "chatting.auth.ApiKey";
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct CreatedApiKey {
+    pub api_key: ApiKey,
+    /// Only available right after creation.
+    pub secret: BearerToken,
+}
+
+/// Who a bearer token was issued to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Principal {
+    /// A user signed in with a password; may do anything the user can.
+    Session { user_id: UserId },
+    /// An API key acting for its user, limited to `scopes`.
+    ApiKey {
+        id: ApiKeyId,
+        user_id: UserId,
+        scopes: Vec<ApiKeyScope>,
+    },
+}
+
+impl Principal {
+    pub fn user_id(&self) -> UserId {
+        match self {
+            Self::Session { user_id } | Self::ApiKey { user_id, .. } => *user_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct AuthenticateParams {
+    pub token: BearerToken,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct RegisterParams {
+    pub handle: UserHandle,
+    pub password: Password,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct LoginParams {
+    /// As typed. Not validated, so that users whose handle predates the current rules can still
+    /// log in.
+    pub handle: String,
+    pub password: Password,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct LogoutParams {
+    /// The session of this token is revoked; the token itself stays valid until it expires.
+    pub token: BearerToken,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct RefreshTokenParams {
+    pub refresh_token: RefreshToken,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct CreateApiKeyParams {
+    pub label: ApiKeyLabel,
+    pub scopes: Vec<ApiKeyScope>,
+    pub caller: UserId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct ListApiKeysParams {
+    pub caller: UserId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct RevokeApiKeyParams {
+    pub id: ApiKeyId,
+    pub caller: UserId,
+}
+
+pub trait AuthService<Context: ?Sized>: Send + Sync + 'static {
+    /// Resolves `token` to whom it was issued to. Session tokens should not need a DB round
+    /// trip for this since it runs on every request.
+    fn authenticate<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: AuthenticateParams,
+    ) -> impl Future<Output = Result<Principal, Failure>> + Send;
+    fn register<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: RegisterParams,
+    ) -> impl Future<Output = Result<Registration, Failure>> + Send;
+    fn login<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: LoginParams,
+    ) -> impl Future<Output = Result<Session, Failure>> + Send;
+    fn logout<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: LogoutParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send;
+    /// Revokes the session of the refresh token and issues a new one.
+    fn refresh_token<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: RefreshTokenParams,
+    ) -> impl Future<Output = Result<Session, Failure>> + Send;
+    fn create_api_key<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: CreateApiKeyParams,
+    ) -> impl Future<Output = Result<CreatedApiKey, Failure>> + Send;
+    fn list_api_keys<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: ListApiKeysParams,
+    ) -> impl Future<Output = Result<Vec<ApiKey>, Failure>> + Send;
+    fn revoke_api_key<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: RevokeApiKeyParams,
+    ) -> impl Future<Output = Result<ApiKey, Failure>> + Send;
+}
+
+pub trait ProvideAuthService: Send + Sync + 'static {
+    type Context: ?Sized;
+    type AuthService: AuthService<Self::Context>;
+
+    fn auth_service(&self) -> &Self::AuthService;
+    fn context(&self) -> &Self::Context;
+
+    fn authenticate(
+        &self,
+        params: AuthenticateParams,
+    ) -> impl Future<Output = Result<Principal, Failure>> + Send {
+        let ctx = self.context();
+        self.auth_service().authenticate(ctx, params)
+    }
+    fn register(
+        &self,
+        params: RegisterParams,
+    ) -> impl Future<Output = Result<Registration, Failure>> + Send {
+        let ctx = self.context();
+        self.auth_service().register(ctx, params)
+    }
+    fn login(&self, params: LoginParams) -> impl Future<Output = Result<Session, Failure>> + Send {
+        let ctx = self.context();
+        self.auth_service().login(ctx, params)
+    }
+    fn logout(&self, params: LogoutParams) -> impl Future<Output = Result<(), Failure>> + Send {
+        let ctx = self.context();
+        self.auth_service().logout(ctx, params)
+    }
+    fn refresh_token(
+        &self,
+        params: RefreshTokenParams,
+    ) -> impl Future<Output = Result<Session, Failure>> + Send {
+        let ctx = self.context();
+        self.auth_service().refresh_token(ctx, params)
+    }
+    fn create_api_key(
+        &self,
+        params: CreateApiKeyParams,
+    ) -> impl Future<Output = Result<CreatedApiKey, Failure>> + Send {
+        let ctx = self.context();
+        self.auth_service().create_api_key(ctx, params)
+    }
+    fn list_api_keys(
+        &self,
+        params: ListApiKeysParams,
+    ) -> impl Future<Output = Result<Vec<ApiKey>, Failure>> + Send {
+        let ctx = self.context();
+        self.auth_service().list_api_keys(ctx, params)
+    }
+    fn revoke_api_key(
+        &self,
+        params: RevokeApiKeyParams,
+    ) -> impl Future<Output = Result<ApiKey, Failure>> + Send {
+        let ctx = self.context();
+        self.auth_service().revoke_api_key(ctx, params)
+    }
+}
+
+impl<T> ProvideAuthService for std::sync::Arc<T>
+where
+    T: ProvideAuthService,
+{
+    type Context = T::Context;
+    type AuthService = T::AuthService;
+
+    fn context(&self) -> &Self::Context {
+        T::context(self)
+    }
+    fn auth_service(&self) -> &Self::AuthService {
+        T::auth_service(self)
+    }
+}
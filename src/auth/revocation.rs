@@ -0,0 +1,65 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use chrono::TimeDelta;
+
+use crate::user::UserId;
+
+/// Users whose unexpired access tokens are no longer accepted, kept in process.
+///
+/// Access tokens are verified without the DB, so revoking sessions only stops new tokens from
+/// being issued. This closes the gap until the old ones expire. Other instances do not see the
+/// entries; there, tokens stay valid for at most the access token TTL.
+#[derive(Debug, Clone)]
+pub struct RevocationList {
+    /// When each user was revoked. Tokens issued until then are rejected.
+    revoked: Arc<Mutex<HashMap<UserId, super::Timestamp>>>,
+    access_token_ttl: TimeDelta,
+}
+
+impl RevocationList {
+    /// Entries are kept for `access_token_ttl`, after which every token they cover has expired.
+    pub fn new(access_token_ttl: TimeDelta) -> Self {
+        Self {
+            revoked: Default::default(),
+            access_token_ttl,
+        }
+    }
+
+    /// Rejects access tokens of `user_id` issued until now. Call it once the change that took
+    /// their access away is committed.
+    pub fn revoke_user(&self, user_id: UserId) {
+        let now = chrono::Utc::now();
+        let mut revoked = self.revoked.lock().unwrap_or_else(PoisonError::into_inner);
+        revoked.retain(|_, &mut at| at + self.access_token_ttl > now);
+        revoked.insert(user_id, now);
+    }
+
+    /// Whether a token of `user_id` issued at `issued_at` (Unix seconds) was revoked.
+    pub(crate) fn is_revoked(&self, user_id: UserId, issued_at: i64) -> bool {
+        let revoked = self.revoked.lock().unwrap_or_else(PoisonError::into_inner);
+        revoked
+            .get(&user_id)
+            .is_some_and(|at| issued_at <= at.timestamp())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_only_tokens_issued_before_revocation() {
+        let revocations = RevocationList::new(TimeDelta::minutes(15));
+        let user_id = UserId(uuid::Uuid::now_v7());
+        let other = UserId(uuid::Uuid::now_v7());
+        let issued_at = chrono::Utc::now().timestamp();
+        assert!(!revocations.is_revoked(user_id, issued_at));
+        revocations.revoke_user(user_id);
+        assert!(revocations.is_revoked(user_id, issued_at));
+        assert!(!revocations.is_revoked(user_id, issued_at + 60));
+        assert!(!revocations.is_revoked(other, issued_at));
+    }
+}
@@ -0,0 +1,691 @@
+use anyhow::Context;
+use argon2::{
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+    password_hash::{SaltString, rand_core::OsRng, rand_core::RngCore},
+};
+use base64::Engine;
+use chrono::TimeDelta;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, MySqlConnection};
+use unicode_normalization::UnicodeNormalization;
+use uuid::Uuid;
+
+use super::{
+    revocation::RevocationList,
+    token::{AccessClaims, Keyring},
+};
+use crate::{
+    db::Database,
+    error::{Failure, lock_failed},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Impl {
+    access_token_ttl: TimeDelta,
+    refresh_token_ttl: TimeDelta,
+}
+
+impl Default for Impl {
+    fn default() -> Self {
+        Self {
+            // access tokens are only revoked in process, so they are kept short-lived
+            access_token_ttl: TimeDelta::minutes(15),
+            refresh_token_ttl: TimeDelta::days(30),
+        }
+    }
+}
+
+impl Impl {
+    /// How long an access token stays valid; size [`RevocationList`]s with it.
+    pub fn access_token_ttl(&self) -> TimeDelta {
+        self.access_token_ttl
+    }
+}
+
+// MARK: helper types
+
+#[derive(Debug, Clone, Hash, Deserialize, Serialize, FromRow)]
+struct ApiKeyRow {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub label: String,
+    pub scopes: String,
+    pub created_at: super::Timestamp,
+    pub last_used_at: Option<super::Timestamp>,
+    pub revoked_at: Option<super::Timestamp>,
+}
+
+impl TryFrom<ApiKeyRow> for super::ApiKey {
+    type Error = anyhow::Error;
+
+    fn try_from(value: ApiKeyRow) -> Result<Self, Self::Error> {
+        let api_key = Self {
+            id: super::ApiKeyId(value.id),
+            user_id: super::UserId(value.user_id),
+            label: super::ApiKeyLabel(value.label),
+            scopes: decode_scopes(&value.scopes)?,
+            created_at: value.created_at,
+            last_used_at: value.last_used_at,
+            revoked_at: value.revoked_at,
+        };
+        Ok(api_key)
+    }
+}
+
+fn encode_scope(value: super::ApiKeyScope) -> &'static str {
+    match value {
+        super::ApiKeyScope::MessagesRead => "messages:read",
+        super::ApiKeyScope::MessagesWrite => "messages:write",
+        super::ApiKeyScope::ChannelsRead => "channels:read",
+        super::ApiKeyScope::ChannelsWrite => "channels:write",
+        super::ApiKeyScope::UsersRead => "users:read",
+    }
+}
+
+fn decode_scope(value: &str) -> anyhow::Result<super::ApiKeyScope> {
+    match value {
+        "messages:read" => Ok(super::ApiKeyScope::MessagesRead),
+        "messages:write" => Ok(super::ApiKeyScope::MessagesWrite),
+        "channels:read" => Ok(super::ApiKeyScope::ChannelsRead),
+        "channels:write" => Ok(super::ApiKeyScope::ChannelsWrite),
+        "users:read" => Ok(super::ApiKeyScope::UsersRead),
+        _ => anyhow::bail!("Unknown API key scope: {value}"),
+    }
+}
+
+/// `SET` columns are read and written as comma separated strings.
+fn encode_scopes(value: &[super::ApiKeyScope]) -> String {
+    value
+        .iter()
+        .map(|s| encode_scope(*s))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn decode_scopes(value: &str) -> anyhow::Result<Vec<super::ApiKeyScope>> {
+    value
+        .split(',')
+        .filter(|s| !s.is_empty())
+        .map(decode_scope)
+        .collect()
+}
+
+// MARK: helper fns
+
+const PASSWORD_MIN_CHARS: usize = 8;
+const PASSWORD_MAX_CHARS: usize = 1024;
+
+fn validate_password(password: &super::Password) -> Result<(), Failure> {
+    let len = password.0.chars().count();
+    if len < PASSWORD_MIN_CHARS {
+        let message = format!("Password must be at least {PASSWORD_MIN_CHARS} characters");
+        return Err(Failure::reject_invalid_field("password", message));
+    }
+    if len > PASSWORD_MAX_CHARS {
+        let message = format!("Password must be at most {PASSWORD_MAX_CHARS} characters");
+        return Err(Failure::reject_invalid_field("password", message));
+    }
+    Ok(())
+}
+
+/// Argon2 is deliberately slow, so it runs off the async workers.
+async fn hash_password(password: super::Password) -> Result<String, Failure> {
+    let hash = tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.0.as_bytes(), &salt)
+            .map(|h| h.to_string())
+            .map_err(|e| anyhow::anyhow!("Failed to hash a password: {e}"))
+    })
+    .await
+    .context("Password hashing panicked")??;
+    Ok(hash)
+}
+
+/// Verified against when a handle has no credentials, so that it takes as long as a wrong
+/// password. A random password hashed with the [`Argon2::default`] parameters.
+const DUMMY_PASSWORD_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$oG5Gc7MmjRVLgE2KvbP2Tw$ikOq6XlUkaA+ab2+YrTAkK7WNZkO/VHjlYhUtX1J3wk";
+
+async fn verify_password(password: super::Password, hash: String) -> Result<bool, Failure> {
+    let verified = tokio::task::spawn_blocking(move || {
+        let hash = PasswordHash::new(&hash)
+            .map_err(|e| anyhow::anyhow!("Malformed password hash in DB: {e}"))?;
+        let verified = Argon2::default()
+            .verify_password(password.0.as_bytes(), &hash)
+            .is_ok();
+        anyhow::Ok(verified)
+    })
+    .await
+    .context("Password verification panicked")??;
+    Ok(verified)
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Tells API keys apart from signed session tokens.
+const API_KEY_PREFIX: &str = "chk_";
+const API_KEY_LABEL_MAX_CHARS: usize = 255;
+
+fn digest_token(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
+
+async fn issue_session(
+    conn: &mut MySqlConnection,
+    keyring: &Keyring,
+    config: &Impl,
+    user_id: Uuid,
+) -> Result<super::Session, Failure> {
+    let id = Uuid::now_v7();
+    let refresh_token = generate_token();
+    let now = chrono::Utc::now();
+    let access_token_expires_at = now + config.access_token_ttl;
+    let refresh_token_expires_at = now + config.refresh_token_ttl;
+    sqlx::query(
+        r#"
+        INSERT INTO `sessions` (
+            `id`, `user_id`, `refresh_token_hash`, `refresh_token_expires_at`, `created_at`
+        )
+        VALUES (?, ?, ?, ?, NOW())
+    "#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(digest_token(&refresh_token))
+    .bind(refresh_token_expires_at)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to create a session to DB")?;
+    let access_token = keyring.sign(&AccessClaims {
+        sub: user_id,
+        sid: id,
+        iat: now.timestamp(),
+        exp: access_token_expires_at.timestamp(),
+    })?;
+    let session = super::Session {
+        user_id: super::UserId(user_id),
+        access_token,
+        access_token_expires_at,
+        refresh_token: super::RefreshToken(refresh_token),
+        refresh_token_expires_at,
+    };
+    Ok(session)
+}
+
+async fn register(
+    db: &impl Database,
+    keyring: &Keyring,
+    config: &Impl,
+    request: super::RegisterParams,
+) -> Result<super::Registration, Failure> {
+    let super::RegisterParams { handle, password } = request;
+    validate_password(&password)?;
+    let password_hash = hash_password(password).await?;
+    let mut tx = db.begin().await?;
+    let user = crate::user::create_user(
+        &mut tx,
+        crate::user::CreateUserParams {
+            handle,
+            display_name: None,
+        },
+    )
+    .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO `credentials` (`user_id`, `password_hash`, `created_at`, `updated_at`)
+        VALUES (?, ?, NOW(), NOW())
+    "#,
+    )
+    .bind(user.id.0)
+    .bind(password_hash)
+    .execute(&mut *tx)
+    .await
+    .context("Failed to create credentials to DB")?;
+    let session = issue_session(&mut tx, keyring, config, user.id.0).await?;
+    tx.commit().await?;
+    Ok(super::Registration { user, session })
+}
+
+async fn find_credential(
+    conn: &mut MySqlConnection,
+    handle: &str,
+) -> Result<Option<(Uuid, String)>, Failure> {
+    let credential = sqlx::query_as(
+        r#"
+        SELECT `c`.`user_id`, `c`.`password_hash`
+        FROM `credentials` AS `c`
+        INNER JOIN `users` AS `u` ON `u`.`id` = `c`.`user_id`
+        WHERE `u`.`handle` = ? AND `u`.`deleted_at` IS NULL
+    "#,
+    )
+    .bind(handle)
+    .fetch_optional(&mut *conn)
+    .await
+    .context("Failed to fetch credentials from DB")?;
+    Ok(credential)
+}
+
+async fn login(
+    db: &impl Database,
+    keyring: &Keyring,
+    config: &Impl,
+    request: super::LoginParams,
+) -> Result<Option<super::Session>, Failure> {
+    let super::LoginParams { handle, password } = request;
+    // handles are stored NFKC-normalized, except for those older than that rule
+    let mut conn = db.conn().await?;
+    let mut credential = find_credential(&mut conn, &handle).await?;
+    let normalized: String = handle.nfkc().collect();
+    if credential.is_none() && normalized != handle {
+        credential = find_credential(&mut conn, &normalized).await?;
+    }
+    // not held while hashing
+    drop(conn);
+    let Some((user_id, password_hash)) = credential else {
+        // otherwise the response time tells which handles exist
+        verify_password(password, DUMMY_PASSWORD_HASH.to_owned()).await?;
+        return Ok(None);
+    };
+    if !verify_password(password, password_hash).await? {
+        return Ok(None);
+    }
+    let mut conn = db.conn().await?;
+    let session = issue_session(&mut conn, keyring, config, user_id).await?;
+    Ok(Some(session))
+}
+
+/// Revokes the session so its refresh token stops working. The access token stays valid
+/// until it expires.
+async fn logout(
+    conn: &mut MySqlConnection,
+    keyring: &Keyring,
+    request: super::LogoutParams,
+) -> Result<bool, Failure> {
+    let super::LogoutParams { token } = request;
+    let AccessClaims { sid, .. } = keyring.verify(&token, chrono::Utc::now())?;
+    let revoked = sqlx::query(
+        r#"
+        UPDATE `sessions`
+        SET `revoked_at` = NOW()
+        WHERE `id` = ? AND `revoked_at` IS NULL
+    "#,
+    )
+    .bind(sid)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to revoke a session in DB")?
+    .rows_affected();
+    Ok(revoked > 0)
+}
+
+/// Inside the caller's transaction.
+async fn refresh_token(
+    conn: &mut MySqlConnection,
+    keyring: &Keyring,
+    config: &Impl,
+    request: super::RefreshTokenParams,
+) -> Result<Option<super::Session>, Failure> {
+    let super::RefreshTokenParams {
+        refresh_token: super::RefreshToken(refresh_token),
+    } = request;
+    let session: Option<(Uuid, Uuid)> = sqlx::query_as(
+        r#"
+        SELECT `id`, `user_id` FROM `sessions`
+        WHERE `refresh_token_hash` = ?
+        AND `revoked_at` IS NULL
+        AND `refresh_token_expires_at` > NOW()
+        FOR UPDATE
+    "#,
+    )
+    .bind(digest_token(&refresh_token))
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| lock_failed(e, "Failed to fetch a session from DB"))?;
+    let Some((session_id, user_id)) = session else {
+        return Ok(None);
+    };
+    // a refresh token is usable once
+    sqlx::query(r#"UPDATE `sessions` SET `revoked_at` = NOW() WHERE `id` = ?"#)
+        .bind(session_id)
+        .execute(&mut *conn)
+        .await
+        .context("Failed to revoke a session in DB")?;
+    let session = issue_session(conn, keyring, config, user_id).await?;
+    Ok(Some(session))
+}
+
+/// Signs the user out everywhere, inside the caller's transaction. Access tokens already handed
+/// out stay valid until they expire. Returns how many sessions and API keys were revoked.
+pub(crate) async fn revoke_user_credentials(
+    conn: &mut MySqlConnection,
+    user_id: Uuid,
+) -> Result<(u64, u64), Failure> {
+    let sessions = sqlx::query(
+        r#"UPDATE `sessions` SET `revoked_at` = NOW() WHERE `user_id` = ? AND `revoked_at` IS NULL"#,
+    )
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to revoke sessions in DB")?
+    .rows_affected();
+    let api_keys = sqlx::query(
+        r#"UPDATE `api_keys` SET `revoked_at` = NOW() WHERE `user_id` = ? AND `revoked_at` IS NULL"#,
+    )
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to revoke API keys in DB")?
+    .rows_affected();
+    Ok((sessions, api_keys))
+}
+
+/// Removes everything the auth domain keeps about the user, inside the caller's transaction.
+pub(crate) async fn delete_user_credentials(
+    conn: &mut MySqlConnection,
+    user_id: Uuid,
+) -> Result<(), Failure> {
+    for (table, context) in [
+        ("credentials", "Failed to delete credentials from DB"),
+        ("sessions", "Failed to delete sessions from DB"),
+        ("api_keys", "Failed to delete API keys from DB"),
+    ] {
+        sqlx::query(&format!("DELETE FROM `{table}` WHERE `user_id` = ?"))
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await
+            .context(context)?;
+    }
+    Ok(())
+}
+
+fn validate_api_key_label(label: &super::ApiKeyLabel) -> Result<(), Failure> {
+    let len = label.0.trim().chars().count();
+    if len == 0 {
+        return Err(Failure::reject_invalid_field(
+            "label",
+            "API key label must not be empty",
+        ));
+    }
+    if len > API_KEY_LABEL_MAX_CHARS {
+        let message = format!("API key label must be at most {API_KEY_LABEL_MAX_CHARS} characters");
+        return Err(Failure::reject_invalid_field("label", message));
+    }
+    Ok(())
+}
+
+/// Resolves an API key secret and records its use.
+async fn authenticate_api_key(
+    conn: &mut MySqlConnection,
+    secret: &str,
+) -> Result<Option<super::ApiKey>, Failure> {
+    let row: Option<ApiKeyRow> = sqlx::query_as(
+        r#"
+        SELECT `id`, `user_id`, `label`, `scopes`, `created_at`, `last_used_at`, `revoked_at`
+        FROM `api_keys`
+        WHERE `key_hash` = ? AND `revoked_at` IS NULL
+    "#,
+    )
+    .bind(digest_token(secret))
+    .fetch_optional(&mut *conn)
+    .await
+    .context("Failed to fetch an API key from DB")?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let api_key = super::ApiKey::try_from(row)?;
+    sqlx::query(r#"UPDATE `api_keys` SET `last_used_at` = NOW() WHERE `id` = ?"#)
+        .bind(api_key.id.0)
+        .execute(&mut *conn)
+        .await
+        .context("Failed to update an API key in DB")?;
+    Ok(Some(api_key))
+}
+
+async fn get_api_key(
+    conn: &mut MySqlConnection,
+    id: Uuid,
+) -> Result<Option<super::ApiKey>, Failure> {
+    let row: Option<ApiKeyRow> = sqlx::query_as(
+        r#"
+        SELECT `id`, `user_id`, `label`, `scopes`, `created_at`, `last_used_at`, `revoked_at`
+        FROM `api_keys`
+        WHERE `id` = ?
+    "#,
+    )
+    .bind(id)
+    .fetch_optional(&mut *conn)
+    .await
+    .context("Failed to fetch an API key from DB")?;
+    Ok(row.map(super::ApiKey::try_from).transpose()?)
+}
+
+async fn create_api_key(
+    conn: &mut MySqlConnection,
+    request: super::CreateApiKeyParams,
+) -> Result<super::CreatedApiKey, Failure> {
+    let super::CreateApiKeyParams {
+        label,
+        mut scopes,
+        caller,
+    } = request;
+    validate_api_key_label(&label)?;
+    scopes.sort();
+    scopes.dedup();
+    if scopes.is_empty() {
+        return Err(Failure::reject_invalid_field(
+            "scopes",
+            "API key needs at least one scope",
+        ));
+    }
+    let id = Uuid::now_v7();
+    let secret = format!("{API_KEY_PREFIX}{}", generate_token());
+    sqlx::query(
+        r#"
+        INSERT INTO `api_keys` (`id`, `user_id`, `label`, `key_hash`, `scopes`, `created_at`)
+        VALUES (?, ?, ?, ?, ?, NOW())
+    "#,
+    )
+    .bind(id)
+    .bind(caller.0)
+    .bind(label.0.trim())
+    .bind(digest_token(&secret))
+    .bind(encode_scopes(&scopes))
+    .execute(&mut *conn)
+    .await
+    .context("Failed to create an API key to DB")?;
+    let api_key = get_api_key(conn, id)
+        .await?
+        .context("Created API key disappeared")?;
+    let created = super::CreatedApiKey {
+        api_key,
+        secret: super::BearerToken(secret),
+    };
+    Ok(created)
+}
+
+async fn list_api_keys(
+    conn: &mut MySqlConnection,
+    request: super::ListApiKeysParams,
+) -> Result<Vec<super::ApiKey>, Failure> {
+    let super::ListApiKeysParams { caller } = request;
+    let rows: Vec<ApiKeyRow> = sqlx::query_as(
+        r#"
+        SELECT `id`, `user_id`, `label`, `scopes`, `created_at`, `last_used_at`, `revoked_at`
+        FROM `api_keys`
+        WHERE `user_id` = ?
+        ORDER BY `id` ASC
+    "#,
+    )
+    .bind(caller.0)
+    .fetch_all(&mut *conn)
+    .await
+    .context("Failed to fetch API keys from DB")?;
+    let api_keys = rows
+        .into_iter()
+        .map(super::ApiKey::try_from)
+        .collect::<anyhow::Result<_>>()?;
+    Ok(api_keys)
+}
+
+async fn revoke_api_key(
+    conn: &mut MySqlConnection,
+    request: super::RevokeApiKeyParams,
+) -> Result<Option<super::ApiKey>, Failure> {
+    let super::RevokeApiKeyParams {
+        id: super::ApiKeyId(id),
+        caller,
+    } = request;
+    // keys of other users are reported as missing so their ids cannot be probed
+    let Some(api_key) = get_api_key(conn, id).await? else {
+        return Ok(None);
+    };
+    if api_key.user_id != caller {
+        return Ok(None);
+    }
+    if api_key.is_revoked() {
+        return Ok(Some(api_key));
+    }
+    sqlx::query(r#"UPDATE `api_keys` SET `revoked_at` = NOW() WHERE `id` = ?"#)
+        .bind(id)
+        .execute(&mut *conn)
+        .await
+        .context("Failed to revoke an API key in DB")?;
+    get_api_key(conn, id).await
+}
+
+// MARK: impl AuthService
+
+impl<Ctx> super::AuthService<Ctx> for Impl
+where
+    Ctx: Database + AsRef<Keyring> + AsRef<RevocationList> + Send + Sync,
+{
+    async fn authenticate<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::AuthenticateParams,
+    ) -> Result<super::Principal, Failure> {
+        let super::AuthenticateParams { token } = request;
+        if token.0.starts_with(API_KEY_PREFIX) {
+            let super::ApiKey {
+                id,
+                user_id,
+                scopes,
+                ..
+            } = authenticate_api_key(&mut *ctx.conn().await?, &token.0)
+                .await?
+                .ok_or_else(|| Failure::reject_unauthenticated("Invalid or revoked API key"))?;
+            return Ok(super::Principal::ApiKey {
+                id,
+                user_id,
+                scopes,
+            });
+        }
+        let keyring: &Keyring = ctx.as_ref();
+        let AccessClaims { sub, iat, .. } = keyring.verify(&token, chrono::Utc::now())?;
+        let revocations: &RevocationList = ctx.as_ref();
+        if revocations.is_revoked(super::UserId(sub), iat) {
+            return Err(Failure::reject_unauthenticated("Invalid or expired token"));
+        }
+        Ok(super::Principal::Session {
+            user_id: super::UserId(sub),
+        })
+    }
+
+    async fn register<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::RegisterParams,
+    ) -> Result<super::Registration, Failure> {
+        register(ctx, ctx.as_ref(), self, request).await
+    }
+
+    async fn login<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::LoginParams,
+    ) -> Result<super::Session, Failure> {
+        login(ctx, ctx.as_ref(), self, request)
+            .await?
+            .ok_or_else(|| Failure::reject_unauthenticated("Wrong handle or password"))
+    }
+
+    async fn logout<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::LogoutParams,
+    ) -> Result<(), Failure> {
+        let mut conn = ctx.conn().await?;
+        if !logout(&mut conn, ctx.as_ref(), request).await? {
+            return Err(Failure::reject_unauthenticated("Invalid or expired token"));
+        }
+        Ok(())
+    }
+
+    async fn refresh_token<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::RefreshTokenParams,
+    ) -> Result<super::Session, Failure> {
+        let mut tx = ctx.begin().await?;
+        let session = refresh_token(&mut tx, ctx.as_ref(), self, request)
+            .await?
+            .ok_or_else(|| Failure::reject_unauthenticated("Invalid or expired refresh token"))?;
+        tx.commit().await?;
+        Ok(session)
+    }
+
+    async fn create_api_key<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::CreateApiKeyParams,
+    ) -> Result<super::CreatedApiKey, Failure> {
+        let mut conn = ctx.conn().await?;
+        create_api_key(&mut conn, request).await
+    }
+
+    async fn list_api_keys<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::ListApiKeysParams,
+    ) -> Result<Vec<super::ApiKey>, Failure> {
+        let mut conn = ctx.conn().await?;
+        list_api_keys(&mut conn, request).await
+    }
+
+    async fn revoke_api_key<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::RevokeApiKeyParams,
+    ) -> Result<super::ApiKey, Failure> {
+        let id = request.id;
+        let mut conn = ctx.conn().await?;
+        revoke_api_key(&mut conn, request).await?.ok_or_else(|| {
+            Failure::reject_resource_not_found(
+                "API key not found",
+                super::ApiKey::RESOURCE_TYPE,
+                id.0.to_string(),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dummy_password_hash_costs_as_much_as_a_real_one() {
+        let hash = PasswordHash::new(DUMMY_PASSWORD_HASH).unwrap();
+        let argon2 = Argon2::default();
+        assert_eq!(hash.algorithm, argon2::Algorithm::default().ident());
+        let (params, default) = (argon2::Params::try_from(&hash).unwrap(), argon2.params());
+        assert_eq!(params.m_cost(), default.m_cost());
+        assert_eq!(params.t_cost(), default.t_cost());
+        assert_eq!(params.p_cost(), default.p_cost());
+    }
+}
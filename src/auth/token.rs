@@ -0,0 +1,290 @@
+use std::{collections::HashMap, str::FromStr};
+
+use anyhow::Context;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use password_hash::rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::error::Failure;
+
+const ALGORITHM: &str = "HS256";
+const MIN_SECRET_LEN: usize = 32;
+
+/// An HMAC-SHA256 secret identified by `kid` in the token header.
+#[derive(Clone)]
+pub struct SigningKey {
+    id: String,
+    secret: Vec<u8>,
+}
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SigningKey {
+    pub fn new(id: impl Into<String>, secret: Vec<u8>) -> anyhow::Result<Self> {
+        let id = id.into();
+        anyhow::ensure!(!id.is_empty(), "Signing key id must not be empty");
+        anyhow::ensure!(
+            secret.len() >= MIN_SECRET_LEN,
+            "Signing key {id} must be at least {MIN_SECRET_LEN} bytes"
+        );
+        Ok(Self { id, secret })
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        Hmac::new_from_slice(&self.secret).expect("HMAC accepts keys of any length")
+    }
+}
+
+/// Keys that access tokens are signed and verified with.
+///
+/// Tokens are always signed with the active key; the others only verify. To rotate, put a new
+/// key in front and keep the previous one until the tokens it signed have expired.
+#[derive(Debug, Clone)]
+pub struct Keyring {
+    active: SigningKey,
+    keys: HashMap<String, SigningKey>,
+}
+
+impl Keyring {
+    /// The first key becomes the active one.
+    pub fn new(keys: impl IntoIterator<Item = SigningKey>) -> anyhow::Result<Self> {
+        let mut keys = keys.into_iter();
+        let active = keys.next().context("Keyring needs at least one key")?;
+        let mut ring = HashMap::from([(active.id.clone(), active.clone())]);
+        for key in keys {
+            anyhow::ensure!(
+                !ring.contains_key(&key.id),
+                "Duplicate signing key id {}",
+                key.id
+            );
+            ring.insert(key.id.clone(), key);
+        }
+        Ok(Self { active, keys: ring })
+    }
+
+    /// A single random key. Tokens do not survive a restart and are not accepted by other
+    /// instances, so this is only meant for local development.
+    pub fn ephemeral() -> Self {
+        let mut secret = vec![0u8; MIN_SECRET_LEN];
+        OsRng.fill_bytes(&mut secret);
+        let key = SigningKey {
+            id: "ephemeral".to_owned(),
+            secret,
+        };
+        Self::new([key]).expect("a single key is a valid keyring")
+    }
+
+    pub(crate) fn sign(&self, claims: &AccessClaims) -> Result<super::BearerToken, Failure> {
+        let header = Header {
+            alg: ALGORITHM.to_owned(),
+            typ: "JWT".to_owned(),
+            kid: self.active.id.clone(),
+        };
+        let header = serde_json::to_vec(&header).context("Failed to encode a token header")?;
+        let claims = serde_json::to_vec(claims).context("Failed to encode token claims")?;
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header),
+            URL_SAFE_NO_PAD.encode(claims)
+        );
+        let mut mac = self.active.mac();
+        mac.update(signing_input.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        Ok(super::BearerToken(format!("{signing_input}.{signature}")))
+    }
+
+    /// Checks the signature and expiry of `token` without any I/O.
+    pub(crate) fn verify(
+        &self,
+        token: &super::BearerToken,
+        now: super::Timestamp,
+    ) -> Result<AccessClaims, Failure> {
+        let invalid = || Failure::reject_unauthenticated("Invalid or expired token");
+
+        let (signing_input, signature) = token.0.rsplit_once('.').ok_or_else(invalid)?;
+        let (header, claims) = signing_input.split_once('.').ok_or_else(invalid)?;
+        let header: Header = decode_segment(header).ok_or_else(invalid)?;
+        if header.alg != ALGORITHM {
+            return Err(invalid());
+        }
+        let key = self.keys.get(&header.kid).ok_or_else(invalid)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid())?;
+        let mut mac = key.mac();
+        mac.update(signing_input.as_bytes());
+        mac.verify_slice(&signature).map_err(|_| invalid())?;
+        let claims: AccessClaims = decode_segment(claims).ok_or_else(invalid)?;
+        if claims.exp <= now.timestamp() {
+            return Err(invalid());
+        }
+        Ok(claims)
+    }
+}
+
+/// `kid=secret` pairs separated by commas, secrets in unpadded base64url.
+impl FromStr for Keyring {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = s
+            .split(',')
+            .map(str::trim)
+            .filter(|k| !k.is_empty())
+            .map(|k| {
+                let (id, secret) = k
+                    .split_once('=')
+                    .context("Signing key must be written as `kid=secret`")?;
+                let secret = URL_SAFE_NO_PAD
+                    .decode(secret)
+                    .with_context(|| format!("Signing key {id} is not base64url"))?;
+                SigningKey::new(id, secret)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Self::new(keys)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Header {
+    alg: String,
+    typ: String,
+    kid: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct AccessClaims {
+    /// The user the token was issued to.
+    pub sub: Uuid,
+    /// The session the token belongs to, revoked on logout.
+    pub sid: Uuid,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+fn decode_segment<T: serde::de::DeserializeOwned>(segment: &str) -> Option<T> {
+    let bytes = URL_SAFE_NO_PAD.decode(segment).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{auth::BearerToken, error::RejectKind, prelude::Timestamp};
+
+    fn key(id: &str, byte: u8) -> SigningKey {
+        SigningKey::new(id, vec![byte; MIN_SECRET_LEN]).unwrap()
+    }
+
+    fn claims(now: Timestamp) -> AccessClaims {
+        AccessClaims {
+            sub: Uuid::now_v7(),
+            sid: Uuid::now_v7(),
+            iat: now.timestamp(),
+            exp: now.timestamp() + 60,
+        }
+    }
+
+    fn is_rejected(result: Result<AccessClaims, Failure>) -> bool {
+        matches!(result, Err(Failure::Reject(r)) if r.kind() == RejectKind::Unauthenticated)
+    }
+
+    #[test]
+    fn verifies_what_it_signed() {
+        let now = chrono::Utc::now();
+        let keyring = Keyring::new([key("a", 1)]).unwrap();
+        let claims = claims(now);
+        let token = keyring.sign(&claims).unwrap();
+        assert_eq!(keyring.verify(&token, now).unwrap(), claims);
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let now = chrono::Utc::now();
+        let keyring = Keyring::new([key("a", 1)]).unwrap();
+        let token = keyring.sign(&claims(now)).unwrap();
+        let expired = now + chrono::TimeDelta::seconds(60);
+        assert!(is_rejected(keyring.verify(&token, expired)));
+    }
+
+    #[test]
+    fn rejects_unknown_key_ids() {
+        let now = chrono::Utc::now();
+        let token = Keyring::new([key("a", 1)])
+            .unwrap()
+            .sign(&claims(now))
+            .unwrap();
+        // same secret under another id
+        let other = Keyring::new([key("b", 1)]).unwrap();
+        assert!(is_rejected(other.verify(&token, now)));
+    }
+
+    #[test]
+    fn rejects_tampered_tokens() {
+        let now = chrono::Utc::now();
+        let keyring = Keyring::new([key("a", 1)]).unwrap();
+        let BearerToken(token) = keyring.sign(&claims(now)).unwrap();
+        let (rest, signature) = token.rsplit_once('.').unwrap();
+        let (header, _) = rest.split_once('.').unwrap();
+
+        let forged = serde_json::to_vec(&claims(now)).unwrap();
+        let forged = format!("{header}.{}.{signature}", URL_SAFE_NO_PAD.encode(forged));
+        assert!(is_rejected(keyring.verify(&BearerToken(forged), now)));
+
+        let mut signature = URL_SAFE_NO_PAD.decode(signature).unwrap();
+        signature[0] ^= 1;
+        let resigned = format!("{rest}.{}", URL_SAFE_NO_PAD.encode(signature));
+        assert!(is_rejected(keyring.verify(&BearerToken(resigned), now)));
+    }
+
+    #[test]
+    fn rotation_keeps_old_tokens_until_the_key_is_dropped() {
+        let now = chrono::Utc::now();
+        let old = Keyring::new([key("old", 1)]).unwrap();
+        let token = old.sign(&claims(now)).unwrap();
+
+        let rotated = Keyring::new([key("new", 2), key("old", 1)]).unwrap();
+        assert!(rotated.verify(&token, now).is_ok());
+        let fresh = rotated.sign(&claims(now)).unwrap();
+        assert!(rotated.verify(&fresh, now).is_ok());
+
+        let retired = Keyring::new([key("new", 2)]).unwrap();
+        assert!(is_rejected(retired.verify(&token, now)));
+        assert!(retired.verify(&fresh, now).is_ok());
+    }
+
+    #[test]
+    fn parses_keys_from_config() {
+        let secret = URL_SAFE_NO_PAD.encode([1u8; MIN_SECRET_LEN]);
+        let other = URL_SAFE_NO_PAD.encode([2u8; MIN_SECRET_LEN]);
+        let keyring: Keyring = format!("new={secret}, old={other}").parse().unwrap();
+        assert_eq!(keyring.active.id, "new");
+        assert_eq!(keyring.keys.len(), 2);
+    }
+
+    #[test]
+    fn rejects_bad_config() {
+        let secret = URL_SAFE_NO_PAD.encode([1u8; MIN_SECRET_LEN]);
+        let short = URL_SAFE_NO_PAD.encode([1u8; MIN_SECRET_LEN - 1]);
+        for config in [
+            String::new(),
+            format!("a={short}"),
+            format!("a={secret},a={secret}"),
+            "a=not base64!".to_owned(),
+            secret.clone(),
+            format!("={secret}"),
+        ] {
+            assert!(
+                config.parse::<Keyring>().is_err(),
+                "{config:?} was accepted"
+            );
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{error::Failure, message::MessageId, user::UserId};
+
+mod svc;
+
+pub use svc::Impl as AuthzServiceImpl;
+pub(crate) use svc::{channel_role, decode_channel_role, encode_channel_role, global_role};
+
+/// Server-wide role of an user.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Deserialize, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum GlobalRole {
+    #[default]
+    User,
+    /// May manage any user and channel. Granted directly in the DB.
+    Admin,
+}
+
+/// Role of a member within a channel. Each role can do everything the ones before it can.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Deserialize, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelRole {
+    #[default]
+    Member,
+    /// May rename the channel, kick members and delete their messages.
+    Moderator,
+    /// May also archive the channel and change roles of members.
+    Owner,
+}
+
+/// An operation whose permission depends on who is asking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Action {
+    UpdateUser(UserId),
+    DeleteUser(UserId),
+    RestoreUser(UserId),
+    /// Looking up users that were deleted.
+    ViewDeletedUsers,
+    UpdateMessage(MessageId),
+    DeleteMessage(MessageId),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct AuthorizeParams {
+    pub action: Action,
+    pub caller: Option<UserId>,
+}
+
+pub trait AuthzService<Context: ?Sized>: Send + Sync + 'static {
+    /// Succeeds if `caller` may perform `action`.
+    fn authorize<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: AuthorizeParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send;
+}
+
+pub trait ProvideAuthzService: Send + Sync + 'static {
+    type Context: ?Sized;
+    type AuthzService: AuthzService<Self::Context>;
+
+    fn authz_service(&self) -> &Self::AuthzService;
+    fn context(&self) -> &Self::Context;
+
+    fn authorize(
+        &self,
+        params: AuthorizeParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        let ctx = self.context();
+        self.authz_service().authorize(ctx, params)
+    }
+}
+
+impl<T> ProvideAuthzService for std::sync::Arc<T>
+where
+    T: ProvideAuthzService,
+{
+    type Context = T::Context;
+    type AuthzService = T::AuthzService;
+
+    fn context(&self) -> &Self::Context {
+        T::context(self)
+    }
+    fn authz_service(&self) -> &Self::AuthzService {
+        T::authz_service(self)
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::Context;
+use sqlx::MySqlConnection;
+use uuid::Uuid;
+
+use crate::{
+    channel::ChannelId,
+    db::Database,
+    error::Failure,
+    message::{MessageId, check_message_access, message_not_found},
+    user::UserId,
+};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Impl;
+
+// MARK: helper fns
+
+fn decode_global_role(value: &str) -> anyhow::Result<super::GlobalRole> {
+    match value {
+        "user" => Ok(super::GlobalRole::User),
+        "admin" => Ok(super::GlobalRole::Admin),
+        _ => anyhow::bail!("Unknown global role: {value}"),
+    }
+}
+
+pub(crate) fn encode_channel_role(value: super::ChannelRole) -> &'static str {
+    match value {
+        super::ChannelRole::Member => "member",
+        super::ChannelRole::Moderator => "moderator",
+        super::ChannelRole::Owner => "owner",
+    }
+}
+
+pub(crate) fn decode_channel_role(value: &str) -> anyhow::Result<super::ChannelRole> {
+    match value {
+        "member" => Ok(super::ChannelRole::Member),
+        "moderator" => Ok(super::ChannelRole::Moderator),
+        "owner" => Ok(super::ChannelRole::Owner),
+        _ => anyhow::bail!("Unknown channel role: {value}"),
+    }
+}
+
+/// Unknown and deleted users have no privileges.
+pub(crate) async fn global_role(
+    conn: &mut MySqlConnection,
+    user_id: UserId,
+) -> Result<super::GlobalRole, Failure> {
+    let role: Option<String> =
+        sqlx::query_scalar(r#"SELECT `role` FROM `users` WHERE `id` = ? AND `deleted_at` IS NULL"#)
+            .bind(user_id.0)
+            .fetch_optional(&mut *conn)
+            .await
+            .context("Failed to fetch an user role from DB")?;
+    let role = role.as_deref().map(decode_global_role).transpose()?;
+    Ok(role.unwrap_or_default())
+}
+
+/// `None` if the user is not a member of the channel.
+pub(crate) async fn channel_role(
+    conn: &mut MySqlConnection,
+    channel_id: Uuid,
+    user_id: UserId,
+) -> Result<Option<super::ChannelRole>, Failure> {
+    let role: Option<String> = sqlx::query_scalar(
+        r#"SELECT `role` FROM `channel_members` WHERE `channel_id` = ? AND `user_id` = ?"#,
+    )
+    .bind(channel_id)
+    .bind(user_id.0)
+    .fetch_optional(&mut *conn)
+    .await
+    .context("Failed to fetch a channel role from DB")?;
+    let role = role.as_deref().map(decode_channel_role).transpose()?;
+    Ok(role)
+}
+
+async fn authorize_user_change(
+    conn: &mut MySqlConnection,
+    target: UserId,
+    caller: UserId,
+) -> Result<bool, Failure> {
+    if target == caller {
+        return Ok(true);
+    }
+    Ok(global_role(conn, caller).await? == super::GlobalRole::Admin)
+}
+
+/// The channel and author of a message `caller` can read. Messages they cannot read are
+/// reported as missing, like those that do not exist.
+async fn message_owner(
+    conn: &mut MySqlConnection,
+    id: MessageId,
+    caller: UserId,
+) -> Result<(Uuid, Option<UserId>), Failure> {
+    let row: Option<(Uuid, Option<Uuid>)> =
+        sqlx::query_as(r#"SELECT `channel_id`, `created_by` FROM `messages` WHERE `id` = ?"#)
+            .bind(id.0)
+            .fetch_optional(&mut *conn)
+            .await
+            .context("Failed to fetch a message from DB")?;
+    let (channel_id, created_by) = row.ok_or_else(|| message_not_found(id))?;
+    check_message_access(conn, id, ChannelId(channel_id), Some(caller)).await?;
+    Ok((channel_id, created_by.map(UserId)))
+}
+
+async fn authorize(
+    conn: &mut MySqlConnection,
+    action: super::Action,
+    caller: UserId,
+) -> Result<bool, Failure> {
+    match action {
+        super::Action::UpdateUser(id) | super::Action::DeleteUser(id) => {
+            authorize_user_change(conn, id, caller).await
+        }
+        super::Action::RestoreUser(_) | super::Action::ViewDeletedUsers => {
+            Ok(global_role(conn, caller).await? == super::GlobalRole::Admin)
+        }
+        // only authors may put words in their own mouth
+        super::Action::UpdateMessage(id) => {
+            let (_, created_by) = message_owner(conn, id, caller).await?;
+            Ok(created_by == Some(caller))
+        }
+        super::Action::DeleteMessage(id) => {
+            let (channel_id, created_by) = message_owner(conn, id, caller).await?;
+            if created_by == Some(caller) {
+                return Ok(true);
+            }
+            let role = channel_role(conn, channel_id, caller).await?;
+            if role >= Some(super::ChannelRole::Moderator) {
+                return Ok(true);
+            }
+            Ok(global_role(conn, caller).await? == super::GlobalRole::Admin)
+        }
+    }
+}
+
+// MARK: impl AuthzService
+
+impl<Ctx> super::AuthzService<Ctx> for Impl
+where
+    Ctx: Database,
+{
+    async fn authorize<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::AuthorizeParams,
+    ) -> Result<(), Failure> {
+        let super::AuthorizeParams { action, caller } = request;
+        let caller =
+            caller.ok_or_else(|| Failure::reject_unauthenticated("Authentication required"))?;
+        let mut conn = ctx.conn().await?;
+        if !authorize(&mut conn, action, caller).await? {
+            tracing::info!(?action, caller = %caller.0, "Permission denied");
+            return Err(Failure::reject_permission_denied(
+                "Not allowed to perform this operation",
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,388 @@
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{
+    authz::ChannelRole,
+    error::{Failure, Reject},
+    prelude::Timestamp,
+    user::UserId,
+};
+
+mod svc;
+
+pub use svc::Impl as ChannelServiceImpl;
+pub(crate) use svc::{
+    ChannelRemoval, check_channel_access, delete_user_memberships, remove_user_from_channels,
+    visible_channel_ids,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct ChannelId(pub uuid::Uuid);
+
+/// 1 to 100 characters without control characters, NFKC-normalized; surrounding whitespace is
+/// dropped. Direct channels have no name and store an empty one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ChannelName(String);
+
+impl ChannelName {
+    pub const MAX_CHARS: usize = 100;
+
+    pub fn new(value: impl Into<String>) -> Result<Self, Reject> {
+        let invalid = |description: String| {
+            Reject::bad_request(description.clone()).with_field_violation("name", description)
+        };
+
+        let value: String = value.into().nfkc().collect();
+        let value = value.trim();
+        let len = value.chars().count();
+        if !(1..=Self::MAX_CHARS).contains(&len) {
+            let max = Self::MAX_CHARS;
+            return Err(invalid(format!(
+                "Channel name must be 1 to {max} characters"
+            )));
+        }
+        if value.chars().any(char::is_control) {
+            return Err(invalid(
+                "Channel name must not contain control characters".to_string(),
+            ));
+        }
+        Ok(Self(value.to_string()))
+    }
+
+    pub(crate) fn from_stored(value: String) -> Self {
+        Self(value)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for ChannelName {
+    type Error = Reject;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl From<ChannelName> for String {
+    fn from(value: ChannelName) -> Self {
+        value.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelVisibility {
+    /// Anyone can read and join.
+    #[default]
+    Public,
+    /// Only members can read; new members must be invited.
+    Private,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelKind {
+    /// A named channel anyone can create.
+    #[default]
+    Group,
+    /// A private conversation between two users. Its membership never changes.
+    Direct,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Channel {
+    pub id: ChannelId,
+    pub name: ChannelName,
+    pub kind: ChannelKind,
+    pub visibility: ChannelVisibility,
+    pub created_at: Timestamp,
+    pub updated_at: Timestamp,
+    pub archived_at: Option<Timestamp>,
+}
+
+impl Channel {
+    /// Names channels in reject details.
+    pub const RESOURCE_TYPE: &str = "chatting.channel.Channel";
+
+    pub fn is_archived(&self) -> bool {
+        self.archived_at.is_some()
+    }
+
+    pub fn is_direct(&self) -> bool {
+        self.kind == ChannelKind::Direct
+    }
+}
+
+pub(crate) fn channel_not_found(ChannelId(id): ChannelId) -> Failure {
+    Failure::reject_resource_not_found("Channel not found", Channel::RESOURCE_TYPE, id.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct ChannelMember {
+    pub channel_id: ChannelId,
+    pub user_id: UserId,
+    pub role: ChannelRole,
+    pub joined_at: Timestamp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct GetChannelParams {
+    pub id: ChannelId,
+    pub caller: Option<UserId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct CreateChannelParams {
+    pub name: ChannelName,
+    pub visibility: ChannelVisibility,
+    /// Joins the channel as its owner.
+    pub created_by: UserId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct RenameChannelParams {
+    pub id: ChannelId,
+    pub name: ChannelName,
+    pub caller: Option<UserId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct ArchiveChannelParams {
+    pub id: ChannelId,
+    pub caller: Option<UserId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct ListChannelsParams {
+    pub include_archived: bool,
+    pub caller: Option<UserId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct JoinChannelParams {
+    pub id: ChannelId,
+    pub caller: UserId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct LeaveChannelParams {
+    pub id: ChannelId,
+    pub caller: UserId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct InviteToChannelParams {
+    pub id: ChannelId,
+    pub user_id: UserId,
+    pub caller: UserId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct KickFromChannelParams {
+    pub id: ChannelId,
+    pub user_id: UserId,
+    pub caller: UserId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct SetChannelMemberRoleParams {
+    pub id: ChannelId,
+    pub user_id: UserId,
+    pub role: ChannelRole,
+    pub caller: UserId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct OpenDirectChannelParams {
+    /// The other participant.
+    pub user_id: UserId,
+    pub caller: UserId,
+}
+
+pub trait ChannelService<Context: ?Sized>: Send + Sync + 'static {
+    fn get_channel<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: GetChannelParams,
+    ) -> impl Future<Output = Result<Channel, Failure>> + Send;
+    fn create_channel<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: CreateChannelParams,
+    ) -> impl Future<Output = Result<Channel, Failure>> + Send;
+    fn rename_channel<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: RenameChannelParams,
+    ) -> impl Future<Output = Result<Channel, Failure>> + Send;
+    fn archive_channel<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: ArchiveChannelParams,
+    ) -> impl Future<Output = Result<Channel, Failure>> + Send;
+    fn list_channels<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: ListChannelsParams,
+    ) -> impl Future<Output = Result<Vec<Channel>, Failure>> + Send;
+    fn join_channel<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: JoinChannelParams,
+    ) -> impl Future<Output = Result<ChannelMember, Failure>> + Send;
+    fn leave_channel<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: LeaveChannelParams,
+    ) -> impl Future<Output = Result<ChannelMember, Failure>> + Send;
+    fn invite_to_channel<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: InviteToChannelParams,
+    ) -> impl Future<Output = Result<ChannelMember, Failure>> + Send;
+    fn kick_from_channel<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: KickFromChannelParams,
+    ) -> impl Future<Output = Result<ChannelMember, Failure>> + Send;
+    fn set_channel_member_role<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: SetChannelMemberRoleParams,
+    ) -> impl Future<Output = Result<ChannelMember, Failure>> + Send;
+    fn open_direct_channel<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: OpenDirectChannelParams,
+    ) -> impl Future<Output = Result<Channel, Failure>> + Send;
+}
+
+pub trait ProvideChannelService: Send + Sync + 'static {
+    type Context: ?Sized;
+    type ChannelService: ChannelService<Self::Context>;
+
+    fn channel_service(&self) -> &Self::ChannelService;
+    fn context(&self) -> &Self::Context;
+
+    fn get_channel(
+        &self,
+        params: GetChannelParams,
+    ) -> impl Future<Output = Result<Channel, Failure>> + Send {
+        let ctx = self.context();
+        self.channel_service().get_channel(ctx, params)
+    }
+    fn create_channel(
+        &self,
+        params: CreateChannelParams,
+    ) -> impl Future<Output = Result<Channel, Failure>> + Send {
+        let ctx = self.context();
+        self.channel_service().create_channel(ctx, params)
+    }
+    fn rename_channel(
+        &self,
+        params: RenameChannelParams,
+    ) -> impl Future<Output = Result<Channel, Failure>> + Send {
+        let ctx = self.context();
+        self.channel_service().rename_channel(ctx, params)
+    }
+    fn archive_channel(
+        &self,
+        params: ArchiveChannelParams,
+    ) -> impl Future<Output = Result<Channel, Failure>> + Send {
+        let ctx = self.context();
+        self.channel_service().archive_channel(ctx, params)
+    }
+    fn list_channels(
+        &self,
+        params: ListChannelsParams,
+    ) -> impl Future<Output = Result<Vec<Channel>, Failure>> + Send {
+        let ctx = self.context();
+        self.channel_service().list_channels(ctx, params)
+    }
+    fn join_channel(
+        &self,
+        params: JoinChannelParams,
+    ) -> impl Future<Output = Result<ChannelMember, Failure>> + Send {
+        let ctx = self.context();
+        self.channel_service().join_channel(ctx, params)
+    }
+    fn leave_channel(
+        &self,
+        params: LeaveChannelParams,
+    ) -> impl Future<Output = Result<ChannelMember, Failure>> + Send {
+        let ctx = self.context();
+        self.channel_service().leave_channel(ctx, params)
+    }
+    fn invite_to_channel(
+        &self,
+        params: InviteToChannelParams,
+    ) -> impl Future<Output = Result<ChannelMember, Failure>> + Send {
+        let ctx = self.context();
+        self.channel_service().invite_to_channel(ctx, params)
+    }
+    fn kick_from_channel(
+        &self,
+        params: KickFromChannelParams,
+    ) -> impl Future<Output = Result<ChannelMember, Failure>> + Send {
+        let ctx = self.context();
+        self.channel_service().kick_from_channel(ctx, params)
+    }
+    fn set_channel_member_role(
+        &self,
+        params: SetChannelMemberRoleParams,
+    ) -> impl Future<Output = Result<ChannelMember, Failure>> + Send {
+        let ctx = self.context();
+        self.channel_service().set_channel_member_role(ctx, params)
+    }
+    fn open_direct_channel(
+        &self,
+        params: OpenDirectChannelParams,
+    ) -> impl Future<Output = Result<Channel, Failure>> + Send {
+        let ctx = self.context();
+        self.channel_service().open_direct_channel(ctx, params)
+    }
+}
+
+impl<T> ProvideChannelService for std::sync::Arc<T>
+where
+    T: ProvideChannelService,
+{
+    type Context = T::Context;
+    type ChannelService = T::ChannelService;
+
+    fn context(&self) -> &Self::Context {
+        T::context(self)
+    }
+    fn channel_service(&self) -> &Self::ChannelService {
+        T::channel_service(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_is_trimmed_and_bounded() {
+        assert_eq!(ChannelName::new("  general ").unwrap().as_str(), "general");
+        assert!(ChannelName::new("").is_err());
+        assert!(ChannelName::new(" \t ").is_err());
+        assert!(ChannelName::new("a".repeat(ChannelName::MAX_CHARS)).is_ok());
+        let reject = ChannelName::new("a".repeat(ChannelName::MAX_CHARS + 1)).unwrap_err();
+        assert_eq!(reject.details().field_violations[0].field, "name");
+    }
+
+    #[test]
+    fn name_is_nfkc_normalized_and_rejects_control_characters() {
+        assert_eq!(
+            ChannelName::new("ｇｅｎｅｒａｌ").unwrap().as_str(),
+            "general"
+        );
+        assert!(ChannelName::new("gen\neral").is_err());
+    }
+}
@@ -0,0 +1,974 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySqlConnection};
+use uuid::Uuid;
+
+use crate::{
+    authz::{
+        ChannelRole, GlobalRole, channel_role, decode_channel_role, encode_channel_role,
+        global_role,
+    },
+    channel::channel_not_found,
+    db::Database,
+    error::{Failure, lock_failed},
+    message::MessageHub,
+    user::user_not_found,
+};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Impl;
+
+// MARK: helper types
+
+#[derive(Debug, Clone, Hash, Deserialize, Serialize, FromRow)]
+struct ChannelRow {
+    pub id: Uuid,
+    pub name: String,
+    pub kind: String,
+    pub visibility: String,
+    pub created_at: super::Timestamp,
+    pub updated_at: super::Timestamp,
+    pub archived_at: Option<super::Timestamp>,
+}
+
+impl TryFrom<ChannelRow> for super::Channel {
+    type Error = anyhow::Error;
+
+    fn try_from(value: ChannelRow) -> Result<Self, Self::Error> {
+        let kind = decode_kind(&value.kind)?;
+        let visibility = decode_visibility(&value.visibility)?;
+        let channel = Self {
+            id: super::ChannelId(value.id),
+            name: super::ChannelName::from_stored(value.name),
+            kind,
+            visibility,
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+            archived_at: value.archived_at,
+        };
+        Ok(channel)
+    }
+}
+
+#[derive(Debug, Clone, Hash, Deserialize, Serialize, FromRow)]
+struct ChannelMemberRow {
+    pub channel_id: Uuid,
+    pub user_id: Uuid,
+    pub role: String,
+    pub joined_at: super::Timestamp,
+}
+
+impl TryFrom<ChannelMemberRow> for super::ChannelMember {
+    type Error = anyhow::Error;
+
+    fn try_from(value: ChannelMemberRow) -> Result<Self, Self::Error> {
+        let member = Self {
+            channel_id: super::ChannelId(value.channel_id),
+            user_id: super::UserId(value.user_id),
+            role: decode_channel_role(&value.role)?,
+            joined_at: value.joined_at,
+        };
+        Ok(member)
+    }
+}
+
+fn encode_visibility(value: super::ChannelVisibility) -> &'static str {
+    match value {
+        super::ChannelVisibility::Public => "public",
+        super::ChannelVisibility::Private => "private",
+    }
+}
+
+fn decode_visibility(value: &str) -> anyhow::Result<super::ChannelVisibility> {
+    match value {
+        "public" => Ok(super::ChannelVisibility::Public),
+        "private" => Ok(super::ChannelVisibility::Private),
+        _ => anyhow::bail!("Unknown channel visibility: {value}"),
+    }
+}
+
+fn decode_kind(value: &str) -> anyhow::Result<super::ChannelKind> {
+    match value {
+        "group" => Ok(super::ChannelKind::Group),
+        "direct" => Ok(super::ChannelKind::Direct),
+        _ => anyhow::bail!("Unknown channel kind: {value}"),
+    }
+}
+
+// MARK: helper fns
+
+async fn get_channel(
+    conn: &mut MySqlConnection,
+    request: super::GetChannelParams,
+) -> Result<Option<super::Channel>, Failure> {
+    let super::GetChannelParams {
+        id: super::ChannelId(id),
+        caller: _,
+    } = request;
+    let channel: Option<ChannelRow> = sqlx::query_as(r#"SELECT * FROM `channels` WHERE `id` = ?"#)
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await
+        .context("Failed to fetch a channel from DB")?;
+    let channel = channel.map(super::Channel::try_from).transpose()?;
+    Ok(channel)
+}
+
+/// Inside the caller's transaction.
+async fn create_channel(
+    conn: &mut MySqlConnection,
+    request: super::CreateChannelParams,
+) -> Result<super::Channel, Failure> {
+    let id = Uuid::now_v7();
+    let super::CreateChannelParams {
+        name,
+        visibility,
+        created_by: super::UserId(created_by),
+    } = request;
+    sqlx::query(
+        r#"
+        INSERT INTO `channels` (`id`, `name`, `visibility`, `created_at`, `updated_at`)
+        VALUES (?, ?, ?, NOW(), NOW())
+    "#,
+    )
+    .bind(id)
+    .bind(String::from(name))
+    .bind(encode_visibility(visibility))
+    .execute(&mut *conn)
+    .await
+    .context("Failed to create a channel to DB")?;
+    sqlx::query(
+        r#"
+        INSERT INTO `channel_members` (`channel_id`, `user_id`, `role`, `joined_at`)
+        VALUES (?, ?, 'owner', NOW())
+    "#,
+    )
+    .bind(id)
+    .bind(created_by)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to add a channel member to DB")?;
+    let channel: ChannelRow = sqlx::query_as(r#"SELECT * FROM `channels` WHERE `id` = ?"#)
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await
+        .context("Failed to fetch a channel from DB")?;
+    let channel = channel.try_into()?;
+    Ok(channel)
+}
+
+/// Locks the channel row until the end of the transaction `conn` is in, so that changes to the
+/// channel go one at a time and the checks before them read its latest state.
+async fn lock_channel(conn: &mut MySqlConnection, id: super::ChannelId) -> Result<(), Failure> {
+    sqlx::query(r#"SELECT `id` FROM `channels` WHERE `id` = ? FOR UPDATE"#)
+        .bind(id.0)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| lock_failed(e, "Failed to fetch a channel from DB"))?;
+    Ok(())
+}
+
+/// Inside the caller's transaction.
+async fn rename_channel(
+    conn: &mut MySqlConnection,
+    request: super::RenameChannelParams,
+) -> Result<Option<super::Channel>, Failure> {
+    let super::RenameChannelParams {
+        id: super::ChannelId(id),
+        name,
+        caller,
+    } = request;
+    sqlx::query(
+        r#"
+        UPDATE `channels`
+        SET `name` = ?, `updated_at` = NOW()
+        WHERE `id` = ?
+    "#,
+    )
+    .bind(String::from(name))
+    .bind(id)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to rename a channel in DB")?;
+    get_channel(
+        conn,
+        super::GetChannelParams {
+            id: super::ChannelId(id),
+            caller,
+        },
+    )
+    .await
+}
+
+/// Inside the caller's transaction.
+async fn archive_channel(
+    conn: &mut MySqlConnection,
+    request: super::ArchiveChannelParams,
+) -> Result<Option<super::Channel>, Failure> {
+    let super::ArchiveChannelParams {
+        id: super::ChannelId(id),
+        caller,
+    } = request;
+    // archiving twice keeps the first timestamp
+    sqlx::query(
+        r#"
+        UPDATE `channels`
+        SET `archived_at` = NOW(), `updated_at` = NOW()
+        WHERE `id` = ? AND `archived_at` IS NULL
+    "#,
+    )
+    .bind(id)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to archive a channel in DB")?;
+    get_channel(
+        conn,
+        super::GetChannelParams {
+            id: super::ChannelId(id),
+            caller,
+        },
+    )
+    .await
+}
+
+async fn list_channels(
+    conn: &mut MySqlConnection,
+    request: super::ListChannelsParams,
+) -> Result<Vec<super::Channel>, Failure> {
+    let super::ListChannelsParams {
+        include_archived,
+        caller,
+    } = request;
+    let channels: Vec<ChannelRow> = sqlx::query_as(
+        r#"
+        SELECT * FROM `channels` AS `c`
+        WHERE (? OR `c`.`archived_at` IS NULL)
+        AND (
+            `c`.`visibility` = 'public'
+            OR EXISTS (
+                SELECT 1 FROM `channel_members` AS `m`
+                WHERE `m`.`channel_id` = `c`.`id` AND `m`.`user_id` = ?
+            )
+        )
+        ORDER BY `c`.`id` ASC
+    "#,
+    )
+    .bind(include_archived)
+    .bind(caller.map(|super::UserId(u)| u))
+    .fetch_all(&mut *conn)
+    .await
+    .context("Failed to fetch channels from DB")?;
+    let channels = channels
+        .into_iter()
+        .map(super::Channel::try_from)
+        .collect::<Result<_, _>>()?;
+    Ok(channels)
+}
+
+async fn get_member(
+    conn: &mut MySqlConnection,
+    channel_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<super::ChannelMember>, Failure> {
+    let member: Option<ChannelMemberRow> = sqlx::query_as(
+        r#"SELECT * FROM `channel_members` WHERE `channel_id` = ? AND `user_id` = ?"#,
+    )
+    .bind(channel_id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .context("Failed to fetch a channel member from DB")?;
+    let member = member.map(super::ChannelMember::try_from).transpose()?;
+    Ok(member)
+}
+
+async fn add_member(
+    conn: &mut MySqlConnection,
+    channel_id: Uuid,
+    user_id: Uuid,
+) -> Result<super::ChannelMember, Failure> {
+    // joining twice keeps the first timestamp
+    sqlx::query(
+        r#"
+        INSERT IGNORE INTO `channel_members` (`channel_id`, `user_id`, `joined_at`)
+        VALUES (?, ?, NOW())
+    "#,
+    )
+    .bind(channel_id)
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to add a channel member to DB")?;
+    let member = get_member(conn, channel_id, user_id)
+        .await?
+        .context("Channel member disappeared right after insertion")?;
+    Ok(member)
+}
+
+/// Locks the member row, and every owner row of the channel, until the end of the transaction
+/// `conn` is in. Owners are locked first and in one go so that two of them stepping down at once
+/// wait on each other instead of deadlocking.
+async fn lock_member(
+    conn: &mut MySqlConnection,
+    channel_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<(super::ChannelMember, usize)>, Failure> {
+    let owners: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+        SELECT `user_id` FROM `channel_members`
+        WHERE `channel_id` = ? AND `role` = 'owner'
+        FOR UPDATE
+    "#,
+    )
+    .bind(channel_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| lock_failed(e, "Failed to fetch channel owners from DB"))?;
+    let member: Option<ChannelMemberRow> = sqlx::query_as(
+        r#"SELECT * FROM `channel_members` WHERE `channel_id` = ? AND `user_id` = ? FOR UPDATE"#,
+    )
+    .bind(channel_id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| lock_failed(e, "Failed to fetch a channel member from DB"))?;
+    let member = member.map(super::ChannelMember::try_from).transpose()?;
+    Ok(member.map(|m| (m, owners.len())))
+}
+
+/// Group channels always keep at least one owner.
+fn check_keeps_owner(member: &super::ChannelMember, owners: usize) -> Result<(), Failure> {
+    if member.role == ChannelRole::Owner && owners <= 1 {
+        return Err(Failure::reject_failed_precondition(
+            "The last owner cannot leave or step down; make another member owner first",
+        ));
+    }
+    Ok(())
+}
+
+/// Inside the caller's transaction.
+async fn remove_member(
+    conn: &mut MySqlConnection,
+    channel_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<super::ChannelMember>, Failure> {
+    let Some((member, owners)) = lock_member(conn, channel_id, user_id).await? else {
+        return Ok(None);
+    };
+    check_keeps_owner(&member, owners)?;
+    sqlx::query(r#"DELETE FROM `channel_members` WHERE `channel_id` = ? AND `user_id` = ?"#)
+        .bind(channel_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await
+        .context("Failed to delete a channel member from DB")?;
+    Ok(Some(member))
+}
+
+/// Inside the caller's transaction.
+async fn set_member_role(
+    conn: &mut MySqlConnection,
+    channel_id: Uuid,
+    user_id: Uuid,
+    role: ChannelRole,
+) -> Result<Option<super::ChannelMember>, Failure> {
+    let Some((mut member, owners)) = lock_member(conn, channel_id, user_id).await? else {
+        return Ok(None);
+    };
+    if role != ChannelRole::Owner {
+        check_keeps_owner(&member, owners)?;
+    }
+    sqlx::query(
+        r#"UPDATE `channel_members` SET `role` = ? WHERE `channel_id` = ? AND `user_id` = ?"#,
+    )
+    .bind(encode_channel_role(role))
+    .bind(channel_id)
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to update a channel member in DB")?;
+    member.role = role;
+    Ok(Some(member))
+}
+
+/// Role of `caller` in the channel for permission checks. Admins act as owners everywhere.
+async fn effective_role(
+    conn: &mut MySqlConnection,
+    channel_id: Uuid,
+    caller: Option<super::UserId>,
+) -> Result<Option<ChannelRole>, Failure> {
+    let Some(caller) = caller else {
+        return Ok(None);
+    };
+    if global_role(conn, caller).await? == GlobalRole::Admin {
+        return Ok(Some(ChannelRole::Owner));
+    }
+    channel_role(conn, channel_id, caller).await
+}
+
+async fn require_role(
+    conn: &mut MySqlConnection,
+    channel_id: Uuid,
+    caller: Option<super::UserId>,
+    role: ChannelRole,
+    message: &str,
+) -> Result<ChannelRole, Failure> {
+    match effective_role(conn, channel_id, caller).await? {
+        Some(r) if r >= role => Ok(r),
+        _ => Err(Failure::reject_permission_denied(message)),
+    }
+}
+
+/// Inside the caller's transaction.
+async fn open_direct_channel(
+    conn: &mut MySqlConnection,
+    request: super::OpenDirectChannelParams,
+) -> Result<super::Channel, Failure> {
+    let super::OpenDirectChannelParams {
+        user_id: super::UserId(user_id),
+        caller: super::UserId(caller),
+    } = request;
+    let (user_low, user_high) = if caller <= user_id {
+        (caller, user_id)
+    } else {
+        (user_id, caller)
+    };
+    let id = Uuid::now_v7();
+    // a concurrent open of the same pair waits on the unique index and then inserts nothing
+    let inserted = sqlx::query(
+        r#"
+        INSERT IGNORE INTO `direct_channels` (`channel_id`, `user_low`, `user_high`)
+        VALUES (?, ?, ?)
+    "#,
+    )
+    .bind(id)
+    .bind(user_low)
+    .bind(user_high)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to create a direct channel to DB")?
+    .rows_affected();
+    if inserted == 0 {
+        // a locking read, so the channel is seen even if it committed after the snapshot
+        let channel: ChannelRow = sqlx::query_as(
+            r#"
+            SELECT `c`.* FROM `channels` AS `c`
+            INNER JOIN `direct_channels` AS `d` ON `d`.`channel_id` = `c`.`id`
+            WHERE `d`.`user_low` = ? AND `d`.`user_high` = ?
+            LOCK IN SHARE MODE
+        "#,
+        )
+        .bind(user_low)
+        .bind(user_high)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| lock_failed(e, "Failed to fetch a direct channel from DB"))?;
+        return Ok(channel.try_into()?);
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO `channels` (`id`, `name`, `kind`, `visibility`, `created_at`, `updated_at`)
+        VALUES (?, '', 'direct', 'private', NOW(), NOW())
+    "#,
+    )
+    .bind(id)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to create a channel to DB")?;
+    // a single row when talking to oneself
+    sqlx::query(
+        r#"
+        INSERT IGNORE INTO `channel_members` (`channel_id`, `user_id`, `joined_at`)
+        VALUES (?, ?, NOW()), (?, ?, NOW())
+    "#,
+    )
+    .bind(id)
+    .bind(user_low)
+    .bind(id)
+    .bind(user_high)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to add channel members to DB")?;
+    let channel = get_channel(
+        conn,
+        super::GetChannelParams {
+            id: super::ChannelId(id),
+            caller: None,
+        },
+    )
+    .await?
+    .context("Direct channel disappeared right after creation")?;
+    Ok(channel)
+}
+
+/// Deleted users count as gone.
+async fn user_exists(conn: &mut MySqlConnection, user_id: Uuid) -> Result<bool, Failure> {
+    let exists: bool = sqlx::query_scalar(
+        r#"SELECT EXISTS (SELECT 1 FROM `users` WHERE `id` = ? AND `deleted_at` IS NULL)"#,
+    )
+    .bind(user_id)
+    .fetch_one(&mut *conn)
+    .await
+    .context("Failed to fetch an user from DB")?;
+    Ok(exists)
+}
+
+/// Fetches the channel if `caller` may read it: public channels are readable by anyone,
+/// private ones only by their members.
+pub(crate) async fn check_channel_access(
+    conn: &mut MySqlConnection,
+    id: super::ChannelId,
+    caller: Option<super::UserId>,
+) -> Result<super::Channel, Failure> {
+    let channel = get_channel(conn, super::GetChannelParams { id, caller })
+        .await?
+        .ok_or_else(|| channel_not_found(id))?;
+    if channel.visibility == super::ChannelVisibility::Public {
+        return Ok(channel);
+    }
+    let is_member = match caller {
+        Some(super::UserId(user_id)) => get_member(conn, id.0, user_id).await?.is_some(),
+        None => false,
+    };
+    if !is_member {
+        return Err(Failure::reject_permission_denied(
+            "Not a member of the private channel",
+        ));
+    }
+    Ok(channel)
+}
+
+/// Every channel `caller` may read, archived ones included.
+pub(crate) async fn visible_channel_ids(
+    conn: &mut MySqlConnection,
+    caller: Option<super::UserId>,
+) -> Result<Vec<super::ChannelId>, Failure> {
+    let ids: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+        SELECT `c`.`id` FROM `channels` AS `c`
+        WHERE `c`.`visibility` = 'public'
+        OR EXISTS (
+            SELECT 1 FROM `channel_members` AS `m`
+            WHERE `m`.`channel_id` = `c`.`id` AND `m`.`user_id` = ?
+        )
+    "#,
+    )
+    .bind(caller.map(|super::UserId(u)| u))
+    .fetch_all(&mut *conn)
+    .await
+    .context("Failed to fetch channels from DB")?;
+    Ok(ids.into_iter().map(super::ChannelId).collect())
+}
+
+/// Ends delivery of a private channel's events to streams of a member who just left, once the
+/// work of `ctx` is committed. Anyone can read public channels, so their streams are left alone.
+fn revoke_stream_access<Ctx>(ctx: &Ctx, channel: &super::Channel, user_id: super::UserId)
+where
+    Ctx: Database + AsRef<MessageHub>,
+{
+    if channel.visibility == super::ChannelVisibility::Private {
+        let hub: &MessageHub = ctx.as_ref();
+        let hub = hub.clone();
+        let channel_id = channel.id;
+        ctx.after_commit(move || hub.revoke_access(user_id, channel_id));
+    }
+}
+
+/// What [`remove_user_from_channels`] did.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ChannelRemoval {
+    pub memberships_removed: u64,
+    /// Group channels whose ownership passed to another member.
+    pub channels_transferred: u64,
+    /// Group channels archived because nobody was left to own them.
+    pub channels_archived: u64,
+}
+
+/// Drops a deleted user from every group channel, inside the caller's transaction. Channels they
+/// solely owned go to the highest ranking, longest standing remaining member. Direct channels
+/// keep the user so the other side keeps the conversation.
+pub(crate) async fn remove_user_from_channels(
+    conn: &mut MySqlConnection,
+    user_id: Uuid,
+) -> Result<ChannelRemoval, Failure> {
+    let mut removal = ChannelRemoval::default();
+    let owned: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+        SELECT `m`.`channel_id`
+        FROM `channel_members` AS `m`
+        INNER JOIN `channels` AS `c` ON `c`.`id` = `m`.`channel_id`
+        WHERE `m`.`user_id` = ? AND `m`.`role` = 'owner' AND `c`.`kind` = 'group'
+        FOR UPDATE
+    "#,
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| lock_failed(e, "Failed to fetch owned channels from DB"))?;
+    for channel_id in owned {
+        // ENUM values sort in declaration order, so `owner` comes first when descending
+        let successor: Option<(Uuid, String)> = sqlx::query_as(
+            r#"
+            SELECT `m`.`user_id`, CAST(`m`.`role` AS CHAR)
+            FROM `channel_members` AS `m`
+            INNER JOIN `users` AS `u` ON `u`.`id` = `m`.`user_id`
+            WHERE `m`.`channel_id` = ? AND `m`.`user_id` <> ? AND `u`.`deleted_at` IS NULL
+            ORDER BY `m`.`role` DESC, `m`.`joined_at` ASC, `m`.`user_id` ASC
+            LIMIT 1
+            FOR UPDATE
+        "#,
+        )
+        .bind(channel_id)
+        .bind(user_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| lock_failed(e, "Failed to fetch channel members from DB"))?;
+        match successor {
+            Some((_, role)) if decode_channel_role(&role)? == ChannelRole::Owner => {}
+            Some((successor, _)) => {
+                sqlx::query(
+                    r#"
+                    UPDATE `channel_members` SET `role` = 'owner'
+                    WHERE `channel_id` = ? AND `user_id` = ?
+                "#,
+                )
+                .bind(channel_id)
+                .bind(successor)
+                .execute(&mut *conn)
+                .await
+                .context("Failed to transfer a channel in DB")?;
+                removal.channels_transferred += 1;
+            }
+            None => {
+                removal.channels_archived += sqlx::query(
+                    r#"
+                    UPDATE `channels` SET `archived_at` = NOW(), `updated_at` = NOW()
+                    WHERE `id` = ? AND `archived_at` IS NULL
+                "#,
+                )
+                .bind(channel_id)
+                .execute(&mut *conn)
+                .await
+                .context("Failed to archive a channel in DB")?
+                .rows_affected();
+            }
+        }
+    }
+    removal.memberships_removed = sqlx::query(
+        r#"
+        DELETE `m` FROM `channel_members` AS `m`
+        INNER JOIN `channels` AS `c` ON `c`.`id` = `m`.`channel_id`
+        WHERE `m`.`user_id` = ? AND `c`.`kind` = 'group'
+    "#,
+    )
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to remove channel memberships from DB")?
+    .rows_affected();
+    Ok(removal)
+}
+
+/// Drops every membership of a purged user, direct channels included, inside the caller's
+/// transaction. Returns how many were removed.
+pub(crate) async fn delete_user_memberships(
+    conn: &mut MySqlConnection,
+    user_id: Uuid,
+) -> Result<u64, Failure> {
+    let deleted = sqlx::query(r#"DELETE FROM `channel_members` WHERE `user_id` = ?"#)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await
+        .context("Failed to remove channel memberships from DB")?
+        .rows_affected();
+    Ok(deleted)
+}
+
+// MARK: impl ChannelService
+
+const DIRECT_MEMBERSHIP_IS_FIXED: &str = "Members of direct channels cannot change";
+
+impl<Ctx> super::ChannelService<Ctx> for Impl
+where
+    Ctx: Database + AsRef<MessageHub> + Send + Sync,
+{
+    async fn get_channel<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::GetChannelParams,
+    ) -> Result<super::Channel, Failure> {
+        let super::GetChannelParams { id, caller } = request;
+        let mut conn = ctx.conn().await?;
+        check_channel_access(&mut conn, id, caller).await
+    }
+
+    async fn create_channel<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::CreateChannelParams,
+    ) -> Result<super::Channel, Failure> {
+        let mut tx = ctx.begin().await?;
+        let channel = create_channel(&mut tx, request).await?;
+        tx.commit().await?;
+        Ok(channel)
+    }
+
+    async fn rename_channel<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::RenameChannelParams,
+    ) -> Result<super::Channel, Failure> {
+        let id = request.id;
+        let mut tx = ctx.begin().await?;
+        lock_channel(&mut tx, id).await?;
+        let channel = check_channel_access(&mut tx, id, request.caller).await?;
+        if channel.is_direct() {
+            return Err(Failure::reject_failed_precondition(
+                "Direct channels cannot be renamed",
+            ));
+        }
+        require_role(
+            &mut tx,
+            id.0,
+            request.caller,
+            ChannelRole::Moderator,
+            "Only moderators can rename the channel",
+        )
+        .await?;
+        let channel = rename_channel(&mut tx, request)
+            .await?
+            .ok_or_else(|| channel_not_found(id))?;
+        tx.commit().await?;
+        Ok(channel)
+    }
+
+    async fn archive_channel<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::ArchiveChannelParams,
+    ) -> Result<super::Channel, Failure> {
+        let id = request.id;
+        let mut tx = ctx.begin().await?;
+        lock_channel(&mut tx, id).await?;
+        check_channel_access(&mut tx, id, request.caller).await?;
+        require_role(
+            &mut tx,
+            id.0,
+            request.caller,
+            ChannelRole::Owner,
+            "Only owners can archive the channel",
+        )
+        .await?;
+        let channel = archive_channel(&mut tx, request)
+            .await?
+            .ok_or_else(|| channel_not_found(id))?;
+        tx.commit().await?;
+        Ok(channel)
+    }
+
+    async fn list_channels<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::ListChannelsParams,
+    ) -> Result<Vec<super::Channel>, Failure> {
+        let mut conn = ctx.conn().await?;
+        list_channels(&mut conn, request).await
+    }
+
+    async fn join_channel<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::JoinChannelParams,
+    ) -> Result<super::ChannelMember, Failure> {
+        let super::JoinChannelParams {
+            id,
+            caller: super::UserId(user_id),
+        } = request;
+        let mut tx = ctx.begin().await?;
+        let channel = get_channel(&mut tx, super::GetChannelParams { id, caller: None })
+            .await?
+            .ok_or_else(|| channel_not_found(id))?;
+        if let Some(member) = get_member(&mut tx, id.0, user_id).await? {
+            return Ok(member);
+        }
+        if channel.visibility == super::ChannelVisibility::Private {
+            return Err(Failure::reject_permission_denied(
+                "Private channels can only be joined by invitation",
+            ));
+        }
+        if channel.is_archived() {
+            return Err(Failure::reject_failed_precondition("Channel is archived"));
+        }
+        let member = add_member(&mut tx, id.0, user_id).await?;
+        tx.commit().await?;
+        Ok(member)
+    }
+
+    async fn leave_channel<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::LeaveChannelParams,
+    ) -> Result<super::ChannelMember, Failure> {
+        let super::LeaveChannelParams {
+            id,
+            caller: super::UserId(user_id),
+        } = request;
+        let mut tx = ctx.begin().await?;
+        let channel = get_channel(&mut tx, super::GetChannelParams { id, caller: None })
+            .await?
+            .ok_or_else(|| channel_not_found(id))?;
+        if channel.is_direct() {
+            return Err(Failure::reject_failed_precondition(
+                DIRECT_MEMBERSHIP_IS_FIXED,
+            ));
+        }
+        let member = remove_member(&mut tx, id.0, user_id)
+            .await?
+            .ok_or_else(|| Failure::reject_not_found("Not a member of the channel"))?;
+        tx.commit().await?;
+        revoke_stream_access(ctx, &channel, member.user_id);
+        Ok(member)
+    }
+
+    async fn invite_to_channel<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::InviteToChannelParams,
+    ) -> Result<super::ChannelMember, Failure> {
+        let super::InviteToChannelParams {
+            id,
+            user_id: super::UserId(user_id),
+            caller: super::UserId(caller),
+        } = request;
+        let mut tx = ctx.begin().await?;
+        let channel = get_channel(&mut tx, super::GetChannelParams { id, caller: None })
+            .await?
+            .ok_or_else(|| channel_not_found(id))?;
+        if get_member(&mut tx, id.0, caller).await?.is_none() {
+            return Err(Failure::reject_permission_denied(
+                "Only members can invite to the channel",
+            ));
+        }
+        if channel.is_direct() {
+            return Err(Failure::reject_failed_precondition(
+                DIRECT_MEMBERSHIP_IS_FIXED,
+            ));
+        }
+        if channel.is_archived() {
+            return Err(Failure::reject_failed_precondition("Channel is archived"));
+        }
+        if !user_exists(&mut tx, user_id).await? {
+            return Err(user_not_found(super::UserId(user_id)));
+        }
+        let member = add_member(&mut tx, id.0, user_id).await?;
+        tx.commit().await?;
+        Ok(member)
+    }
+
+    async fn kick_from_channel<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::KickFromChannelParams,
+    ) -> Result<super::ChannelMember, Failure> {
+        let super::KickFromChannelParams {
+            id,
+            user_id: super::UserId(user_id),
+            caller: super::UserId(caller),
+        } = request;
+        let mut tx = ctx.begin().await?;
+        let channel = get_channel(&mut tx, super::GetChannelParams { id, caller: None })
+            .await?
+            .ok_or_else(|| channel_not_found(id))?;
+        if channel.is_direct() {
+            return Err(Failure::reject_failed_precondition(
+                DIRECT_MEMBERSHIP_IS_FIXED,
+            ));
+        }
+        let caller = super::UserId(caller);
+        let role = require_role(
+            &mut tx,
+            id.0,
+            Some(caller),
+            ChannelRole::Moderator,
+            "Only moderators can kick from the channel",
+        )
+        .await?;
+        let target = get_member(&mut tx, id.0, user_id)
+            .await?
+            .ok_or_else(|| Failure::reject_not_found("Not a member of the channel"))?;
+        // nobody but admins can kick their peers or those above them
+        if target.role >= role && global_role(&mut tx, caller).await? != GlobalRole::Admin {
+            return Err(Failure::reject_permission_denied(
+                "Cannot kick a member with an equal or higher role",
+            ));
+        }
+        let member = remove_member(&mut tx, id.0, user_id)
+            .await?
+            .ok_or_else(|| Failure::reject_not_found("Not a member of the channel"))?;
+        tx.commit().await?;
+        revoke_stream_access(ctx, &channel, member.user_id);
+        Ok(member)
+    }
+
+    async fn set_channel_member_role<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::SetChannelMemberRoleParams,
+    ) -> Result<super::ChannelMember, Failure> {
+        let super::SetChannelMemberRoleParams {
+            id,
+            user_id: super::UserId(user_id),
+            role,
+            caller,
+        } = request;
+        let mut tx = ctx.begin().await?;
+        let channel = get_channel(&mut tx, super::GetChannelParams { id, caller: None })
+            .await?
+            .ok_or_else(|| channel_not_found(id))?;
+        if channel.is_direct() {
+            return Err(Failure::reject_failed_precondition(
+                "Members of direct channels have no roles",
+            ));
+        }
+        let caller_role = require_role(
+            &mut tx,
+            id.0,
+            Some(caller),
+            ChannelRole::Owner,
+            "Only owners can change roles",
+        )
+        .await?;
+        let target = get_member(&mut tx, id.0, user_id)
+            .await?
+            .ok_or_else(|| Failure::reject_not_found("Not a member of the channel"))?;
+        // same rule as kicking, or a co-owner could be demoted and then kicked; stepping down is
+        // left to the last-owner check
+        if target.user_id != caller
+            && target.role >= caller_role
+            && global_role(&mut tx, caller).await? != GlobalRole::Admin
+        {
+            return Err(Failure::reject_permission_denied(
+                "Cannot change the role of a member with an equal or higher role",
+            ));
+        }
+        let member = set_member_role(&mut tx, id.0, user_id, role)
+            .await?
+            .ok_or_else(|| Failure::reject_not_found("Not a member of the channel"))?;
+        tx.commit().await?;
+        Ok(member)
+    }
+
+    async fn open_direct_channel<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::OpenDirectChannelParams,
+    ) -> Result<super::Channel, Failure> {
+        let mut tx = ctx.begin().await?;
+        if !user_exists(&mut tx, request.user_id.0).await? {
+            return Err(user_not_found(request.user_id));
+        }
+        let channel = open_direct_channel(&mut tx, request).await?;
+        tx.commit().await?;
+        Ok(channel)
+    }
+}
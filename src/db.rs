@@ -0,0 +1,259 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::PoisonError,
+};
+
+use anyhow::Context;
+use sqlx::{MySql, MySqlConnection, MySqlPool, Transaction, pool::PoolConnection};
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::error::{Failure, connection_failed};
+
+/// DB access of a service context. Service traits take connections and transactions from here
+/// instead of from a pool, so that a [`TxContext`] can run every call made with it inside one
+/// transaction.
+///
+/// Methods hold at most one [`Conn`] or [`Tx`] at a time; a [`TxContext`] hands out the same
+/// transaction to both and waits for the previous one to be dropped.
+pub trait Database: Send + Sync {
+    /// A connection for reads and single statement writes.
+    fn conn(&self) -> impl Future<Output = Result<Conn<'_>, Failure>> + Send;
+
+    /// A transaction for work that must be applied together.
+    fn begin(&self) -> impl Future<Output = Result<Tx<'_>, Failure>> + Send;
+
+    /// Runs `f` once the work done so far is committed, to publish what it changed. Pool-backed
+    /// contexts commit within each call, so this runs `f` right away.
+    fn after_commit(&self, f: impl FnOnce() + Send + 'static) {
+        f()
+    }
+}
+
+// MARK: Conn
+
+/// A connection from [`Database::conn`]. Derefs to `MySqlConnection`.
+#[derive(Debug)]
+pub struct Conn<'a>(ConnInner<'a>);
+
+#[derive(Debug)]
+enum ConnInner<'a> {
+    Pooled(PoolConnection<MySql>),
+    Joined(MutexGuard<'a, Transaction<'static, MySql>>),
+}
+
+impl Conn<'static> {
+    pub async fn acquire(pool: &MySqlPool) -> Result<Self, Failure> {
+        let conn = pool
+            .acquire()
+            .await
+            .map_err(|e| connection_failed(e, "Failed to acquire a DB connection"))?;
+        Ok(Self(ConnInner::Pooled(conn)))
+    }
+}
+
+impl Deref for Conn<'_> {
+    type Target = MySqlConnection;
+
+    fn deref(&self) -> &Self::Target {
+        match &self.0 {
+            ConnInner::Pooled(conn) => conn,
+            ConnInner::Joined(tx) => tx,
+        }
+    }
+}
+
+impl DerefMut for Conn<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match &mut self.0 {
+            ConnInner::Pooled(conn) => conn,
+            ConnInner::Joined(tx) => tx,
+        }
+    }
+}
+
+// MARK: Tx
+
+/// A DB transaction with error context on begin and commit. Derefs to `MySqlConnection`, which
+/// is what steps shared across domains take, like `user::create_user`.
+///
+/// Dropping it without [`Tx::commit`] rolls everything back. One from a [`TxContext`] is part of
+/// the context's transaction: committing it does nothing, and the context commits or rolls back
+/// everything at once.
+#[derive(Debug)]
+pub struct Tx<'a>(TxInner<'a>);
+
+#[derive(Debug)]
+enum TxInner<'a> {
+    Own(Transaction<'static, MySql>),
+    Joined(MutexGuard<'a, Transaction<'static, MySql>>),
+}
+
+impl Tx<'static> {
+    pub async fn begin(pool: &MySqlPool) -> Result<Self, Failure> {
+        let tx = pool
+            .begin()
+            .await
+            .map_err(|e| connection_failed(e, "Failed to begin a transaction"))?;
+        Ok(Self(TxInner::Own(tx)))
+    }
+}
+
+impl Tx<'_> {
+    pub async fn commit(self) -> Result<(), Failure> {
+        match self.0 {
+            TxInner::Own(tx) => tx
+                .commit()
+                .await
+                .context("Failed to commit a transaction")?,
+            TxInner::Joined(_) => {}
+        }
+        Ok(())
+    }
+}
+
+impl Deref for Tx<'_> {
+    type Target = MySqlConnection;
+
+    fn deref(&self) -> &Self::Target {
+        match &self.0 {
+            TxInner::Own(tx) => tx,
+            TxInner::Joined(tx) => tx,
+        }
+    }
+}
+
+impl DerefMut for Tx<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match &mut self.0 {
+            TxInner::Own(tx) => tx,
+            TxInner::Joined(tx) => tx,
+        }
+    }
+}
+
+// MARK: TxContext
+
+type Hook = Box<dyn FnOnce() + Send>;
+
+/// A service context whose calls all run in one transaction, for work spanning several services
+/// that must be applied together. Everything else is taken from the wrapped context.
+///
+/// Nothing is applied until [`TxContext::commit`]; dropping it rolls everything back. Events the
+/// services publish are held back until then too. A call that fails may leave part of its work
+/// behind, so drop the context after an error instead of committing it.
+pub struct TxContext<'c, Ctx> {
+    ctx: &'c Ctx,
+    tx: Mutex<Transaction<'static, MySql>>,
+    after_commit: std::sync::Mutex<Vec<Hook>>,
+}
+
+impl<'c, Ctx> TxContext<'c, Ctx>
+where
+    Ctx: AsRef<MySqlPool>,
+{
+    pub async fn begin(ctx: &'c Ctx) -> Result<Self, Failure> {
+        let tx = ctx
+            .as_ref()
+            .begin()
+            .await
+            .map_err(|e| connection_failed(e, "Failed to begin a transaction"))?;
+        Ok(Self {
+            ctx,
+            tx: Mutex::new(tx),
+            after_commit: Default::default(),
+        })
+    }
+}
+
+impl<Ctx> TxContext<'_, Ctx> {
+    pub async fn commit(self) -> Result<(), Failure> {
+        self.tx
+            .into_inner()
+            .commit()
+            .await
+            .context("Failed to commit a transaction")?;
+        let hooks = self
+            .after_commit
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        for hook in hooks {
+            hook();
+        }
+        Ok(())
+    }
+}
+
+impl<Ctx> std::fmt::Debug for TxContext<'_, Ctx> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TxContext").finish_non_exhaustive()
+    }
+}
+
+impl<Ctx, T> AsRef<T> for TxContext<'_, Ctx>
+where
+    Ctx: AsRef<T>,
+    T: ?Sized,
+{
+    fn as_ref(&self) -> &T {
+        self.ctx.as_ref()
+    }
+}
+
+impl<Ctx> Database for TxContext<'_, Ctx>
+where
+    Ctx: Sync,
+{
+    async fn conn(&self) -> Result<Conn<'_>, Failure> {
+        Ok(Conn(ConnInner::Joined(self.tx.lock().await)))
+    }
+
+    async fn begin(&self) -> Result<Tx<'_>, Failure> {
+        Ok(Tx(TxInner::Joined(self.tx.lock().await)))
+    }
+
+    fn after_commit(&self, f: impl FnOnce() + Send + 'static) {
+        self.after_commit
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Box::new(f));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::{AuthService, AuthServiceImpl, Keyring, RevocationList},
+        authz::{AuthzService, AuthzServiceImpl},
+        channel::{ChannelService, ChannelServiceImpl},
+        message::{MessageHub, MessageService, MessageServiceImpl},
+        presence::PresenceHub,
+        user::{UserService, UserServiceImpl},
+    };
+
+    /// Compiles only if every DB-backed service takes a [`TxContext`] wherever it takes the
+    /// context it wraps.
+    #[allow(dead_code)]
+    fn services_accept_tx_context<Ctx>()
+    where
+        Ctx: Database
+            + AsRef<MySqlPool>
+            + AsRef<Keyring>
+            + AsRef<RevocationList>
+            + AsRef<MessageHub>
+            + AsRef<PresenceHub>
+            + 'static,
+    {
+        fn auth<S: AuthService<C>, C>() {}
+        fn authz<S: AuthzService<C>, C>() {}
+        fn user<S: UserService<C>, C>() {}
+        fn channel<S: ChannelService<C>, C>() {}
+        fn message<S: MessageService<C>, C>() {}
+
+        auth::<AuthServiceImpl, TxContext<'static, Ctx>>();
+        authz::<AuthzServiceImpl, TxContext<'static, Ctx>>();
+        user::<UserServiceImpl, TxContext<'static, Ctx>>();
+        channel::<ChannelServiceImpl, TxContext<'static, Ctx>>();
+        message::<MessageServiceImpl, TxContext<'static, Ctx>>();
+    }
+}
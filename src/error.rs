@@ -7,6 +7,17 @@ pub enum RejectKind {
     Unauthenticated,
     BadRequest,
     NotFound,
+    PermissionDenied,
+    /// Lost a race with a concurrent operation; retrying may succeed.
+    Aborted,
+    /// The entity to create is already there.
+    AlreadyExists,
+    /// The request is valid but the current state of the resource does not allow it.
+    FailedPrecondition,
+    /// A quota or rate limit was hit.
+    ResourceExhausted,
+    /// A dependency is temporarily down; retrying later may succeed.
+    Unavailable,
 }
 
 impl fmt::Display for RejectKind {
@@ -15,6 +26,12 @@ impl fmt::Display for RejectKind {
             Self::Unauthenticated => "Unauthenticated",
             Self::BadRequest => "Bad request",
             Self::NotFound => "Not found",
+            Self::PermissionDenied => "Permission denied",
+            Self::Aborted => "Aborted",
+            Self::AlreadyExists => "Already exists",
+            Self::FailedPrecondition => "Failed precondition",
+            Self::ResourceExhausted => "Resource exhausted",
+            Self::Unavailable => "Unavailable",
         };
         f.write_str(s)
     }
@@ -26,6 +43,44 @@ impl std::error::Error for Reject {}
 pub struct Reject {
     kind: RejectKind,
     message: String,
+    // boxed to keep `Result<_, Failure>` small
+    details: Box<RejectDetails>,
+}
+
+/// Machine-readable context of a [`Reject`], sent to clients along with the message.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RejectDetails {
+    pub field_violations: Vec<FieldViolation>,
+    pub resource: Option<ResourceInfo>,
+    pub retry_after: Option<std::time::Duration>,
+    pub localized_message: Option<LocalizedMessage>,
+}
+
+impl RejectDetails {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldViolation {
+    /// Path of the request field, e.g. `password` or `scopes`.
+    pub field: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceInfo {
+    /// Full name of the proto message, e.g. `chatting.user.User`.
+    pub resource_type: String,
+    pub resource_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalizedMessage {
+    /// BCP 47 locale, e.g. `ja-JP`.
+    pub locale: String,
+    pub message: String,
 }
 
 impl fmt::Display for Reject {
@@ -39,9 +94,51 @@ impl Reject {
         Self {
             kind,
             message: message.into(),
+            details: Box::default(),
         }
     }
 
+    pub fn with_field_violation(
+        mut self,
+        field: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        self.details.field_violations.push(FieldViolation {
+            field: field.into(),
+            description: description.into(),
+        });
+        self
+    }
+
+    pub fn with_resource(
+        mut self,
+        resource_type: impl Into<String>,
+        resource_name: impl Into<String>,
+    ) -> Self {
+        self.details.resource = Some(ResourceInfo {
+            resource_type: resource_type.into(),
+            resource_name: resource_name.into(),
+        });
+        self
+    }
+
+    pub fn with_retry_after(mut self, delay: std::time::Duration) -> Self {
+        self.details.retry_after = Some(delay);
+        self
+    }
+
+    pub fn with_localized_message(
+        mut self,
+        locale: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        self.details.localized_message = Some(LocalizedMessage {
+            locale: locale.into(),
+            message: message.into(),
+        });
+        self
+    }
+
     pub fn unauthenticated(message: impl Into<String>) -> Self {
         Self::new(RejectKind::Unauthenticated, message)
     }
@@ -54,6 +151,30 @@ impl Reject {
         Self::new(RejectKind::NotFound, message)
     }
 
+    pub fn permission_denied(message: impl Into<String>) -> Self {
+        Self::new(RejectKind::PermissionDenied, message)
+    }
+
+    pub fn aborted(message: impl Into<String>) -> Self {
+        Self::new(RejectKind::Aborted, message)
+    }
+
+    pub fn already_exists(message: impl Into<String>) -> Self {
+        Self::new(RejectKind::AlreadyExists, message)
+    }
+
+    pub fn failed_precondition(message: impl Into<String>) -> Self {
+        Self::new(RejectKind::FailedPrecondition, message)
+    }
+
+    pub fn resource_exhausted(message: impl Into<String>) -> Self {
+        Self::new(RejectKind::ResourceExhausted, message)
+    }
+
+    pub fn unavailable(message: impl Into<String>) -> Self {
+        Self::new(RejectKind::Unavailable, message)
+    }
+
     pub fn kind(&self) -> RejectKind {
         self.kind
     }
@@ -65,6 +186,14 @@ impl Reject {
     pub fn into_message(self) -> String {
         self.message
     }
+
+    pub fn details(&self) -> &RejectDetails {
+        &self.details
+    }
+
+    pub fn into_parts(self) -> (RejectKind, String, RejectDetails) {
+        (self.kind, self.message, *self.details)
+    }
 }
 
 pub enum Failure {
@@ -93,6 +222,52 @@ impl From<anyhow::Error> for Failure {
     }
 }
 
+pub(crate) fn is_unique_violation(error: &sqlx::Error) -> bool {
+    matches!(error, sqlx::Error::Database(e) if e.is_unique_violation())
+}
+
+/// Whether a statement lost a row lock to a concurrent transaction.
+pub(crate) fn is_lock_conflict(error: &sqlx::Error) -> bool {
+    use sqlx::mysql::MySqlDatabaseError;
+
+    /// `ER_LOCK_WAIT_TIMEOUT`
+    const LOCK_WAIT_TIMEOUT: u16 = 1205;
+    /// `ER_LOCK_DEADLOCK`
+    const LOCK_DEADLOCK: u16 = 1213;
+
+    let sqlx::Error::Database(e) = error else {
+        return false;
+    };
+    e.try_downcast_ref::<MySqlDatabaseError>()
+        .is_some_and(|e| matches!(e.number(), LOCK_WAIT_TIMEOUT | LOCK_DEADLOCK))
+}
+
+/// For statements that take row locks: losing the lock becomes [`RejectKind::Aborted`] so the
+/// client retries. Other errors keep `context`.
+pub(crate) fn lock_failed(error: sqlx::Error, context: &'static str) -> Failure {
+    if is_lock_conflict(&error) {
+        tracing::warn!(
+            ?error,
+            context,
+            "Lost a DB lock to a concurrent transaction"
+        );
+        return Failure::reject_aborted("Conflicting concurrent operation; retry");
+    }
+    anyhow::Error::new(error).context(context).into()
+}
+
+/// For taking a connection from the pool: running out of connections becomes
+/// [`RejectKind::Unavailable`]. Other errors keep `context`.
+pub(crate) fn connection_failed(error: sqlx::Error, context: &'static str) -> Failure {
+    if matches!(error, sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed) {
+        tracing::warn!(?error, context, "DB pool has no connection to spare");
+        return Reject::unavailable("Database is unavailable; retry later")
+            .with_retry_after(std::time::Duration::from_secs(1))
+            .into();
+    }
+    anyhow::Error::new(error).context(context).into()
+}
+
 impl Failure {
     pub fn reject(kind: RejectKind, message: impl Into<String>) -> Self {
         Reject::new(kind, message).into()
@@ -106,7 +281,50 @@ impl Failure {
         Reject::bad_request(message).into()
     }
 
+    /// A bad request caused by a single field; `description` doubles as the message.
+    pub fn reject_invalid_field(field: impl Into<String>, description: impl Into<String>) -> Self {
+        let description = description.into();
+        Reject::bad_request(description.clone())
+            .with_field_violation(field, description)
+            .into()
+    }
+
     pub fn reject_not_found(message: impl Into<String>) -> Self {
         Reject::not_found(message).into()
     }
+
+    /// `resource_type` is the full name of the proto message, e.g. `chatting.user.User`.
+    pub fn reject_resource_not_found(
+        message: impl Into<String>,
+        resource_type: impl Into<String>,
+        resource_name: impl Into<String>,
+    ) -> Self {
+        Reject::not_found(message)
+            .with_resource(resource_type, resource_name)
+            .into()
+    }
+
+    pub fn reject_permission_denied(message: impl Into<String>) -> Self {
+        Reject::permission_denied(message).into()
+    }
+
+    pub fn reject_aborted(message: impl Into<String>) -> Self {
+        Reject::aborted(message).into()
+    }
+
+    pub fn reject_already_exists(message: impl Into<String>) -> Self {
+        Reject::already_exists(message).into()
+    }
+
+    pub fn reject_failed_precondition(message: impl Into<String>) -> Self {
+        Reject::failed_precondition(message).into()
+    }
+
+    pub fn reject_resource_exhausted(message: impl Into<String>) -> Self {
+        Reject::resource_exhausted(message).into()
+    }
+
+    pub fn reject_unavailable(message: impl Into<String>) -> Self {
+        Reject::unavailable(message).into()
+    }
 }
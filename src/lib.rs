@@ -1,4 +1,10 @@
+pub mod auth;
+pub mod authz;
+pub mod channel;
+pub mod db;
 pub mod error;
+pub mod message;
 pub mod prelude;
+pub mod presence;
 pub mod router;
 pub mod user;
@@ -1,10 +1,21 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use anyhow::Context;
 use futures::TryFutureExt;
 use sqlx::MySqlPool;
 
-use chatting::user::UserServiceImpl;
+use tokio_util::sync::CancellationToken;
+
+use chatting::{
+    auth::{AuthServiceImpl, Keyring, RevocationList},
+    authz::AuthzServiceImpl,
+    channel::ChannelServiceImpl,
+    db::{Conn, Database, Tx},
+    error::Failure,
+    message::{MessageHub, MessageServiceImpl},
+    presence::{PresenceHub, PresenceServiceImpl},
+    user::{DeletionPolicy, UserServiceImpl, spawn_purge_task},
+};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -16,10 +27,54 @@ async fn main() -> anyhow::Result<()> {
         .or_else(|_| load_mysql_from_env("MARIADB_"))
         .or_else(|_| load_mysql_from_env("NS_MARIADB_"))
         .await?;
-    let user_service = UserServiceImpl;
-    let state = Arc::new(State { pool, user_service });
+    let dev_mode = std::env::var("DEV_MODE").is_ok_and(|v| matches!(v.as_str(), "1" | "true"));
+    if dev_mode {
+        tracing::warn!("DEV_MODE is on; internal error details are sent to clients");
+    }
+    let keyring = load_keyring_from_env()?;
+    let auth_service = AuthServiceImpl::default();
+    let revocations = RevocationList::new(auth_service.access_token_ttl());
+    let authz_service = AuthzServiceImpl;
+    let user_service = UserServiceImpl::new(load_deletion_policy_from_env()?);
+    let channel_service = ChannelServiceImpl;
+    let message_service = MessageServiceImpl;
+    let shutdown = CancellationToken::new();
+    let message_hub = MessageHub::new(MessageHub::DEFAULT_CAPACITY, shutdown.child_token());
+    let presence_service = PresenceServiceImpl;
+    let presence_hub = PresenceHub::new(
+        PresenceHub::DEFAULT_CAPACITY,
+        PresenceHub::DEFAULT_TIMEOUT,
+        shutdown.child_token(),
+    );
+    let user_retention = load_user_retention_from_env()?;
+    let state = Arc::new(State {
+        pool,
+        keyring,
+        revocations,
+        auth_service,
+        authz_service,
+        user_service,
+        channel_service,
+        message_service,
+        message_hub,
+        presence_service,
+        presence_hub,
+    });
     state.migrate().await?;
-    let router = chatting::router::make_router(state);
+    spawn_purge_task(
+        state.pool.clone(),
+        state.message_hub.clone(),
+        user_retention,
+        USER_PURGE_INTERVAL,
+        shutdown.child_token(),
+    );
+    state
+        .presence_hub
+        .spawn_expiry_task(PRESENCE_EXPIRY_INTERVAL);
+    let config = chatting::router::RouterConfig {
+        expose_internal_errors: dev_mode,
+    };
+    let router = chatting::router::make_router(state, config);
     let port: u16 = std::env::var("PORT")
         .unwrap_or_else(|_| 8080.to_string())
         .parse()
@@ -30,7 +85,7 @@ async fn main() -> anyhow::Result<()> {
         .with_context(|| format!("Failed to bind {addr}"))?;
     tracing::info!(%addr, "Listening");
     axum::serve(listener, router)
-        .with_graceful_shutdown(signal())
+        .with_graceful_shutdown(signal(shutdown))
         .await?;
     Ok(())
 }
@@ -38,7 +93,16 @@ async fn main() -> anyhow::Result<()> {
 #[derive(Debug, Clone)]
 struct State {
     pool: MySqlPool,
+    keyring: Keyring,
+    revocations: RevocationList,
+    auth_service: AuthServiceImpl,
+    authz_service: AuthzServiceImpl,
     user_service: UserServiceImpl,
+    channel_service: ChannelServiceImpl,
+    message_service: MessageServiceImpl,
+    message_hub: MessageHub,
+    presence_service: PresenceServiceImpl,
+    presence_hub: PresenceHub,
 }
 
 #[tracing::instrument]
@@ -67,12 +131,60 @@ async fn load_mysql_from_env(prefix: &str) -> anyhow::Result<MySqlPool> {
         .inspect_err(|e| tracing::error!("{e:?}"))
 }
 
-#[tracing::instrument]
-async fn signal() {
+/// `AUTH_SIGNING_KEYS` holds `kid=secret` pairs; the first one signs new tokens.
+fn load_keyring_from_env() -> anyhow::Result<Keyring> {
+    match std::env::var("AUTH_SIGNING_KEYS") {
+        Ok(keys) => keys
+            .parse()
+            .context("Failed to read AUTH_SIGNING_KEYS value"),
+        Err(std::env::VarError::NotPresent) => {
+            tracing::warn!("AUTH_SIGNING_KEYS is not set; tokens are signed with an ephemeral key");
+            Ok(Keyring::ephemeral())
+        }
+        Err(e) => Err(e).context("Failed to read AUTH_SIGNING_KEYS"),
+    }
+}
+
+const USER_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const PRESENCE_EXPIRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// `USER_RETENTION_DAYS` is how long deleted users can be restored before they are purged.
+fn load_user_retention_from_env() -> anyhow::Result<Duration> {
+    const DEFAULT_DAYS: u64 = 30;
+
+    let days = match std::env::var("USER_RETENTION_DAYS") {
+        Ok(days) => days
+            .parse()
+            .context("Failed to read USER_RETENTION_DAYS value")?,
+        Err(std::env::VarError::NotPresent) => DEFAULT_DAYS,
+        Err(e) => return Err(e).context("Failed to read USER_RETENTION_DAYS"),
+    };
+    let secs = days
+        .checked_mul(24 * 60 * 60)
+        .context("USER_RETENTION_DAYS value is too large")?;
+    Ok(Duration::from_secs(secs))
+}
+
+/// `USER_DELETION_MESSAGES` is `keep`, `anonymize` or `delete`; see `MessagePolicy`.
+fn load_deletion_policy_from_env() -> anyhow::Result<DeletionPolicy> {
+    let messages = match std::env::var("USER_DELETION_MESSAGES") {
+        Ok(policy) => policy
+            .parse()
+            .context("Failed to read USER_DELETION_MESSAGES value")?,
+        Err(std::env::VarError::NotPresent) => Default::default(),
+        Err(e) => return Err(e).context("Failed to read USER_DELETION_MESSAGES"),
+    };
+    Ok(DeletionPolicy { messages })
+}
+
+#[tracing::instrument(skip_all)]
+async fn signal(shutdown: CancellationToken) {
     match tokio::signal::ctrl_c().await {
         Ok(()) => tracing::info!("Received ctrl-c"),
         Err(e) => tracing::error!(%e, "Failed to listen ctrl-c"),
     }
+    // close open streams, otherwise graceful shutdown waits for them forever
+    shutdown.cancel();
 }
 
 impl State {
@@ -93,6 +205,64 @@ impl AsRef<MySqlPool> for State {
     }
 }
 
+impl Database for State {
+    async fn conn(&self) -> Result<Conn<'_>, Failure> {
+        Conn::acquire(&self.pool).await
+    }
+
+    async fn begin(&self) -> Result<Tx<'_>, Failure> {
+        Tx::begin(&self.pool).await
+    }
+}
+
+impl AsRef<Keyring> for State {
+    fn as_ref(&self) -> &Keyring {
+        &self.keyring
+    }
+}
+
+impl AsRef<RevocationList> for State {
+    fn as_ref(&self) -> &RevocationList {
+        &self.revocations
+    }
+}
+
+impl AsRef<AuthServiceImpl> for State {
+    fn as_ref(&self) -> &AuthServiceImpl {
+        &self.auth_service
+    }
+}
+
+impl chatting::auth::ProvideAuthService for State {
+    type Context = State;
+    type AuthService = AuthServiceImpl;
+
+    fn auth_service(&self) -> &Self::AuthService {
+        &self.auth_service
+    }
+    fn context(&self) -> &Self::Context {
+        self
+    }
+}
+
+impl AsRef<AuthzServiceImpl> for State {
+    fn as_ref(&self) -> &AuthzServiceImpl {
+        &self.authz_service
+    }
+}
+
+impl chatting::authz::ProvideAuthzService for State {
+    type Context = State;
+    type AuthzService = AuthzServiceImpl;
+
+    fn authz_service(&self) -> &Self::AuthzService {
+        &self.authz_service
+    }
+    fn context(&self) -> &Self::Context {
+        self
+    }
+}
+
 impl AsRef<UserServiceImpl> for State {
     fn as_ref(&self) -> &UserServiceImpl {
         &self.user_service
@@ -110,3 +280,69 @@ impl chatting::user::ProvideUserService for State {
         self
     }
 }
+
+impl AsRef<ChannelServiceImpl> for State {
+    fn as_ref(&self) -> &ChannelServiceImpl {
+        &self.channel_service
+    }
+}
+
+impl chatting::channel::ProvideChannelService for State {
+    type Context = State;
+    type ChannelService = ChannelServiceImpl;
+
+    fn channel_service(&self) -> &Self::ChannelService {
+        &self.channel_service
+    }
+    fn context(&self) -> &Self::Context {
+        self
+    }
+}
+
+impl AsRef<MessageServiceImpl> for State {
+    fn as_ref(&self) -> &MessageServiceImpl {
+        &self.message_service
+    }
+}
+
+impl AsRef<MessageHub> for State {
+    fn as_ref(&self) -> &MessageHub {
+        &self.message_hub
+    }
+}
+
+impl chatting::message::ProvideMessageService for State {
+    type Context = State;
+    type MessageService = MessageServiceImpl;
+
+    fn message_service(&self) -> &Self::MessageService {
+        &self.message_service
+    }
+    fn context(&self) -> &Self::Context {
+        self
+    }
+}
+
+impl AsRef<PresenceServiceImpl> for State {
+    fn as_ref(&self) -> &PresenceServiceImpl {
+        &self.presence_service
+    }
+}
+
+impl AsRef<PresenceHub> for State {
+    fn as_ref(&self) -> &PresenceHub {
+        &self.presence_hub
+    }
+}
+
+impl chatting::presence::ProvidePresenceService for State {
+    type Context = State;
+    type PresenceService = PresenceServiceImpl;
+
+    fn presence_service(&self) -> &Self::PresenceService {
+        &self.presence_service
+    }
+    fn context(&self) -> &Self::Context {
+        self
+    }
+}
@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{channel::ChannelId, error::Failure, prelude::Timestamp, user::UserId};
+
+mod hub;
+mod svc;
+
+pub use hub::Hub as MessageHub;
+pub use svc::Impl as MessageServiceImpl;
+pub(crate) use svc::{anonymize_user_messages, check_message_access, delete_user_messages};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct MessageId(pub uuid::Uuid);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct MessageText(pub String);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Message {
+    pub id: MessageId,
+    pub channel_id: ChannelId,
+    pub text: MessageText,
+    pub created_at: Timestamp,
+    pub updated_at: Timestamp,
+    pub created_by: Option<UserId>,
+    /// Incremented on every update.
+    pub version: u64,
+}
+
+impl Message {
+    /// Names messages in reject details.
+    pub const RESOURCE_TYPE: &str = "chatting.message.Message";
+}
+
+pub(crate) fn message_not_found(MessageId(id): MessageId) -> Failure {
+    Failure::reject_resource_not_found("Message not found", Message::RESOURCE_TYPE, id.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct GetMessageParams {
+    pub id: MessageId,
+    pub caller: Option<UserId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct CreateMessageParams {
+    pub channel_id: ChannelId,
+    pub text: MessageText,
+    pub created_by: UserId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct UpdateMessageParams {
+    pub id: MessageId,
+    /// `None` leaves the text unchanged.
+    pub text: Option<MessageText>,
+    /// Rejects with [`RejectKind::Aborted`](crate::error::RejectKind::Aborted) unless the
+    /// message is still at this version.
+    pub expected_version: Option<u64>,
+    pub caller: Option<UserId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct DeleteMessageParams {
+    pub id: MessageId,
+    /// Rejects with [`RejectKind::Aborted`](crate::error::RejectKind::Aborted) unless the
+    /// message is still at this version.
+    pub expected_version: Option<u64>,
+    pub caller: Option<UserId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct StreamMessagesParams {
+    /// Replays messages created after this one before switching to live events.
+    pub since: Option<MessageId>,
+    /// Only events of these channels are streamed. Empty means every channel `caller` can read.
+    pub channel_ids: Vec<ChannelId>,
+    pub caller: Option<UserId>,
+    /// Whether the stream keeps `caller` present. API keys act for a user who need not be
+    /// around, so their streams should not.
+    pub keep_present: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum MessageEvent {
+    Created(Message),
+    Updated(Message),
+    Deleted(Message),
+}
+
+impl MessageEvent {
+    pub fn message(&self) -> &Message {
+        match self {
+            Self::Created(m) | Self::Updated(m) | Self::Deleted(m) => m,
+        }
+    }
+}
+
+pub type MessageEventStream =
+    std::pin::Pin<Box<dyn futures::Stream<Item = Result<MessageEvent, Failure>> + Send>>;
+
+pub trait MessageService<Context: ?Sized>: Send + Sync + 'static {
+    fn get_message<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: GetMessageParams,
+    ) -> impl Future<Output = Result<Message, Failure>> + Send;
+    fn create_message<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: CreateMessageParams,
+    ) -> impl Future<Output = Result<Message, Failure>> + Send;
+    fn update_message<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: UpdateMessageParams,
+    ) -> impl Future<Output = Result<Message, Failure>> + Send;
+    fn delete_message<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: DeleteMessageParams,
+    ) -> impl Future<Output = Result<Message, Failure>> + Send;
+    fn stream_messages<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: StreamMessagesParams,
+    ) -> impl Future<Output = Result<MessageEventStream, Failure>> + Send;
+}
+
+pub trait ProvideMessageService: Send + Sync + 'static {
+    type Context: ?Sized;
+    type MessageService: MessageService<Self::Context>;
+
+    fn message_service(&self) -> &Self::MessageService;
+    fn context(&self) -> &Self::Context;
+
+    fn get_message(
+        &self,
+        params: GetMessageParams,
+    ) -> impl Future<Output = Result<Message, Failure>> + Send {
+        let ctx = self.context();
+        self.message_service().get_message(ctx, params)
+    }
+    fn create_message(
+        &self,
+        params: CreateMessageParams,
+    ) -> impl Future<Output = Result<Message, Failure>> + Send {
+        let ctx = self.context();
+        self.message_service().create_message(ctx, params)
+    }
+    fn update_message(
+        &self,
+        params: UpdateMessageParams,
+    ) -> impl Future<Output = Result<Message, Failure>> + Send {
+        let ctx = self.context();
+        self.message_service().update_message(ctx, params)
+    }
+    fn delete_message(
+        &self,
+        params: DeleteMessageParams,
+    ) -> impl Future<Output = Result<Message, Failure>> + Send {
+        let ctx = self.context();
+        self.message_service().delete_message(ctx, params)
+    }
+    fn stream_messages(
+        &self,
+        params: StreamMessagesParams,
+    ) -> impl Future<Output = Result<MessageEventStream, Failure>> + Send {
+        let ctx = self.context();
+        self.message_service().stream_messages(ctx, params)
+    }
+}
+
+impl<T> ProvideMessageService for std::sync::Arc<T>
+where
+    T: ProvideMessageService,
+{
+    type Context = T::Context;
+    type MessageService = T::MessageService;
+
+    fn context(&self) -> &Self::Context {
+        T::context(self)
+    }
+    fn message_service(&self) -> &Self::MessageService {
+        T::message_service(self)
+    }
+}
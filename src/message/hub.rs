@@ -0,0 +1,91 @@
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+use crate::{channel::ChannelId, error::Failure, user::UserId};
+
+/// What goes through the hub. Both kinds share one channel so subscribers see them in the
+/// order they were published.
+#[derive(Debug, Clone)]
+pub(super) enum Signal {
+    Event(super::MessageEvent),
+    /// `user_id` can no longer read `channel_id`.
+    Revoked {
+        user_id: UserId,
+        channel_id: ChannelId,
+    },
+    /// `user_id` was deleted, so their streams end.
+    Disconnected {
+        user_id: UserId,
+    },
+}
+
+pub(super) type SignalStream =
+    std::pin::Pin<Box<dyn futures::Stream<Item = Result<Signal, Failure>> + Send>>;
+
+/// In-process fan-out of [`super::MessageEvent`]s to every open message stream.
+#[derive(Debug, Clone)]
+pub struct Hub {
+    sender: broadcast::Sender<Signal>,
+    shutdown: CancellationToken,
+}
+
+impl Hub {
+    pub const DEFAULT_CAPACITY: usize = 1024;
+
+    /// `capacity` is the number of events a subscriber may fall behind before it is
+    /// disconnected. All streams end once `shutdown` is cancelled.
+    pub fn new(capacity: usize, shutdown: CancellationToken) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender, shutdown }
+    }
+
+    pub fn publish(&self, event: super::MessageEvent) {
+        // no receivers is not an error; the event just has nobody to go to
+        let receivers = self.sender.send(Signal::Event(event)).unwrap_or_default();
+        tracing::debug!(receivers, "Published a message event");
+    }
+
+    /// Stops open streams of `user_id` from delivering events of `channel_id`. Call it once
+    /// the change that took their access away is committed.
+    pub fn revoke_access(&self, user_id: UserId, channel_id: ChannelId) {
+        let signal = Signal::Revoked {
+            user_id,
+            channel_id,
+        };
+        let receivers = self.sender.send(signal).unwrap_or_default();
+        tracing::debug!(receivers, user_id = %user_id.0, channel_id = %channel_id.0, "Revoked channel access of streams");
+    }
+
+    /// Ends open streams of `user_id`. Call it once their deletion is committed.
+    pub fn disconnect_user(&self, user_id: UserId) {
+        let signal = Signal::Disconnected { user_id };
+        let receivers = self.sender.send(signal).unwrap_or_default();
+        tracing::debug!(receivers, user_id = %user_id.0, "Disconnected streams of an user");
+    }
+
+    pub(super) fn subscribe(&self) -> SignalStream {
+        let mut receiver = self.sender.subscribe();
+        let shutdown = self.shutdown.clone();
+        let stream = async_stream::stream! {
+            loop {
+                let received = tokio::select! {
+                    () = shutdown.cancelled() => break,
+                    r = receiver.recv() => r,
+                };
+                match received {
+                    Ok(signal) => yield Ok(signal),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "Message stream lagged behind");
+                        let message = format!(
+                            "Stream lagged behind by {skipped} events; reconnect to resume"
+                        );
+                        yield Err(Failure::reject_aborted(message));
+                        break;
+                    }
+                }
+            }
+        };
+        Box::pin(stream)
+    }
+}
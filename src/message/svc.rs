@@ -0,0 +1,536 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySqlConnection, MySqlPool};
+use uuid::Uuid;
+
+use crate::{
+    channel::{check_channel_access, visible_channel_ids},
+    db::Database,
+    error::{Failure, Reject, RejectKind, lock_failed},
+    message::{
+        hub::{Signal, SignalStream},
+        message_not_found,
+    },
+    presence::{PresenceConnection, PresenceHub},
+};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Impl;
+
+// MARK: helper types
+
+#[derive(Debug, Clone, Hash, Deserialize, Serialize, FromRow)]
+struct MessageRow {
+    pub id: Uuid,
+    pub channel_id: Uuid,
+    pub text: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: super::Timestamp,
+    pub updated_at: super::Timestamp,
+    pub version: u64,
+}
+
+impl From<MessageRow> for super::Message {
+    fn from(value: MessageRow) -> Self {
+        Self {
+            id: super::MessageId(value.id),
+            channel_id: super::ChannelId(value.channel_id),
+            text: super::MessageText(value.text),
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+            created_by: value.created_by.map(super::UserId),
+            version: value.version,
+        }
+    }
+}
+
+// MARK: helper fns
+
+fn version_mismatch(id: Uuid) -> Failure {
+    Reject::aborted("Message was modified concurrently")
+        .with_resource(super::Message::RESOURCE_TYPE, id.to_string())
+        .into()
+}
+
+async fn get_message(
+    conn: &mut MySqlConnection,
+    request: super::GetMessageParams,
+) -> Result<Option<super::Message>, Failure> {
+    let super::GetMessageParams {
+        id: super::MessageId(id),
+        caller: _,
+    } = request;
+    let message: Option<MessageRow> = sqlx::query_as(r#"SELECT * FROM `messages` WHERE `id` = ?"#)
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await
+        .context("Failed to fetch a message from DB")?;
+    Ok(message.map(super::Message::from))
+}
+
+async fn create_message(
+    conn: &mut MySqlConnection,
+    request: super::CreateMessageParams,
+) -> Result<super::Message, Failure> {
+    let id = Uuid::now_v7();
+    let super::CreateMessageParams {
+        channel_id: super::ChannelId(channel_id),
+        text: super::MessageText(text),
+        created_by,
+    } = request;
+    sqlx::query(
+        r#"
+        INSERT INTO `messages` (`id`, `channel_id`, `text`, `created_by`, `created_at`, `updated_at`)
+        VALUES (?, ?, ?, ?, NOW(), NOW())
+    "#,
+    )
+    .bind(id)
+    .bind(channel_id)
+    .bind(text)
+    .bind(created_by.0)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to create a message to DB")?;
+    let message: MessageRow = sqlx::query_as(r#"SELECT * FROM `messages` WHERE `id` = ?"#)
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await
+        .context("Failed to fetch a message from DB")?;
+    Ok(message.into())
+}
+
+/// [`check_channel_access`] for the channel of message `id`, except that a message `caller`
+/// cannot read is reported as missing, so its id does not leak.
+pub(crate) async fn check_message_access(
+    conn: &mut MySqlConnection,
+    id: super::MessageId,
+    channel_id: super::ChannelId,
+    caller: Option<super::UserId>,
+) -> Result<(), Failure> {
+    match check_channel_access(conn, channel_id, caller).await {
+        Ok(_) => Ok(()),
+        Err(Failure::Reject(r)) if r.kind() == RejectKind::PermissionDenied => {
+            Err(message_not_found(id))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Locks the message row until the end of the transaction `conn` is in.
+async fn lock_message(
+    conn: &mut MySqlConnection,
+    id: Uuid,
+) -> Result<Option<super::Message>, Failure> {
+    let message: Option<MessageRow> =
+        sqlx::query_as(r#"SELECT * FROM `messages` WHERE `id` = ? FOR UPDATE"#)
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| lock_failed(e, "Failed to fetch a message from DB"))?;
+    Ok(message.map(super::Message::from))
+}
+
+/// Inside the caller's transaction.
+async fn update_message(
+    conn: &mut MySqlConnection,
+    request: super::UpdateMessageParams,
+) -> Result<Option<super::Message>, Failure> {
+    let super::UpdateMessageParams {
+        id: super::MessageId(id),
+        text,
+        expected_version,
+        caller,
+    } = request;
+    let Some(message) = lock_message(conn, id).await? else {
+        return Ok(None);
+    };
+    if expected_version.is_some_and(|v| v != message.version) {
+        return Err(version_mismatch(id));
+    }
+    let Some(super::MessageText(text)) = text else {
+        return Ok(Some(message));
+    };
+    sqlx::query(
+        r#"
+        UPDATE `messages`
+        SET `text` = ?, `updated_at` = NOW(), `version` = `version` + 1
+        WHERE `id` = ?
+    "#,
+    )
+    .bind(text)
+    .bind(id)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to update a message in DB")?;
+    let get_request = super::GetMessageParams {
+        id: super::MessageId(id),
+        caller,
+    };
+    get_message(conn, get_request).await
+}
+
+/// Inside the caller's transaction.
+async fn delete_message(
+    conn: &mut MySqlConnection,
+    request: super::DeleteMessageParams,
+) -> Result<Option<super::Message>, Failure> {
+    let super::DeleteMessageParams {
+        id: super::MessageId(id),
+        expected_version,
+        caller: _,
+    } = request;
+    let Some(message) = lock_message(conn, id).await? else {
+        return Ok(None);
+    };
+    if expected_version.is_some_and(|v| v != message.version) {
+        return Err(version_mismatch(id));
+    }
+    sqlx::query(r#"DELETE FROM `messages` WHERE `id` = ?"#)
+        .bind(id)
+        .execute(&mut *conn)
+        .await
+        .context("Failed to delete a message from DB")?;
+    Ok(Some(message))
+}
+
+/// Messages of `channel_ids` created after `after`, oldest first.
+/// UUIDv7 ids sort by creation time.
+async fn list_messages_after(
+    pool: &MySqlPool,
+    after: Uuid,
+    channel_ids: &[super::ChannelId],
+    limit: u32,
+) -> Result<Vec<super::Message>, Failure> {
+    if channel_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut query = sqlx::QueryBuilder::new(r#"SELECT * FROM `messages` WHERE `id` > "#);
+    query.push_bind(after);
+    query.push(r#" AND `channel_id` IN ("#);
+    let mut ids = query.separated(", ");
+    for super::ChannelId(id) in channel_ids {
+        ids.push_bind(*id);
+    }
+    query.push(")");
+    query.push(r#" ORDER BY `id` ASC LIMIT "#);
+    query.push_bind(limit);
+    let messages: Vec<MessageRow> = query
+        .build_query_as()
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch messages from DB")?;
+    Ok(messages.into_iter().map(super::Message::from).collect())
+}
+
+/// Replays the backlog after `since` as `Created` events, then continues with `live`.
+///
+/// `live` must be subscribed before the backlog is read so nothing created in between is
+/// lost; live `Created` events of messages already replayed are dropped. Ids are generated
+/// before their row commits, so a live message may sort before the last replayed one and is
+/// still delivered.
+/// Edits and deletions that happened while the client was away are not replayed; clients
+/// refetch the messages they show after reconnecting.
+fn replay_then_live(
+    pool: MySqlPool,
+    since: super::MessageId,
+    channel_ids: Vec<super::ChannelId>,
+    mut live: super::MessageEventStream,
+) -> super::MessageEventStream {
+    use futures::StreamExt;
+
+    const REPLAY_BATCH: u32 = 100;
+
+    let stream = async_stream::stream! {
+        let super::MessageId(mut cursor) = since;
+        let mut replayed = HashSet::new();
+        loop {
+            let batch = match list_messages_after(&pool, cursor, &channel_ids, REPLAY_BATCH).await {
+                Ok(batch) => batch,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+            let exhausted = batch.len() < REPLAY_BATCH as usize;
+            for message in batch {
+                cursor = message.id.0;
+                replayed.insert(message.id);
+                yield Ok(super::MessageEvent::Created(message));
+            }
+            if exhausted {
+                break;
+            }
+        }
+        while let Some(event) = live.next().await {
+            // each message is created once, so its id is not needed after the match
+            if let Ok(super::MessageEvent::Created(message)) = &event
+                && replayed.remove(&message.id)
+            {
+                continue;
+            }
+            yield event;
+        }
+    };
+    Box::pin(stream)
+}
+
+/// Keeps events of `channel_ids` only, minus private channels `caller` loses access to while
+/// streaming, and ends once `caller` is deleted. Errors are passed through.
+fn only_channels(
+    mut signals: SignalStream,
+    channel_ids: &[super::ChannelId],
+    caller: Option<super::UserId>,
+) -> super::MessageEventStream {
+    use futures::StreamExt;
+
+    let mut channel_ids: HashSet<_> = channel_ids.iter().copied().collect();
+    let stream = async_stream::stream! {
+        while let Some(signal) = signals.next().await {
+            match signal {
+                Ok(Signal::Event(event)) => {
+                    if channel_ids.contains(&event.message().channel_id) {
+                        yield Ok(event);
+                    }
+                }
+                Ok(Signal::Revoked {
+                    user_id,
+                    channel_id,
+                }) => {
+                    if caller == Some(user_id) {
+                        channel_ids.remove(&channel_id);
+                    }
+                }
+                Ok(Signal::Disconnected { user_id }) => {
+                    if caller == Some(user_id) {
+                        yield Err(Failure::reject_unauthenticated("User has been deleted"));
+                        break;
+                    }
+                }
+                Err(e) => yield Err(e),
+            }
+        }
+    };
+    Box::pin(stream)
+}
+
+/// Keeps the streaming user present for as long as `stream` is alive.
+fn with_presence(
+    mut stream: super::MessageEventStream,
+    connection: PresenceConnection,
+) -> super::MessageEventStream {
+    use futures::StreamExt;
+
+    let stream = async_stream::stream! {
+        // dropped along with the stream
+        let _connection = connection;
+        while let Some(event) = stream.next().await {
+            yield event;
+        }
+    };
+    Box::pin(stream)
+}
+
+async fn lock_user_messages(
+    conn: &mut MySqlConnection,
+    user_id: Uuid,
+) -> Result<Vec<MessageRow>, Failure> {
+    let messages: Vec<MessageRow> = sqlx::query_as(
+        r#"SELECT * FROM `messages` WHERE `created_by` = ? ORDER BY `id` ASC FOR UPDATE"#,
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| lock_failed(e, "Failed to fetch messages from DB"))?;
+    Ok(messages)
+}
+
+/// Detaches messages of a deleted user from them, inside the caller's transaction. Returns the
+/// messages as they are after the change; publish them once the transaction is committed.
+pub(crate) async fn anonymize_user_messages(
+    conn: &mut MySqlConnection,
+    user_id: Uuid,
+) -> Result<Vec<super::Message>, Failure> {
+    let messages = lock_user_messages(conn, user_id).await?;
+    if messages.is_empty() {
+        return Ok(vec![]);
+    }
+    // not an edit, so `updated_at` stays
+    sqlx::query(
+        r#"
+        UPDATE `messages`
+        SET `created_by` = NULL, `version` = `version` + 1, `updated_at` = `updated_at`
+        WHERE `created_by` = ?
+    "#,
+    )
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to anonymize messages in DB")?;
+    let messages = messages
+        .into_iter()
+        .map(|m| MessageRow {
+            created_by: None,
+            version: m.version + 1,
+            ..m
+        })
+        .map(super::Message::from)
+        .collect();
+    Ok(messages)
+}
+
+/// Removes messages of a deleted user, inside the caller's transaction. Returns the removed
+/// messages; publish them once the transaction is committed.
+pub(crate) async fn delete_user_messages(
+    conn: &mut MySqlConnection,
+    user_id: Uuid,
+) -> Result<Vec<super::Message>, Failure> {
+    let messages = lock_user_messages(conn, user_id).await?;
+    if messages.is_empty() {
+        return Ok(vec![]);
+    }
+    sqlx::query(r#"DELETE FROM `messages` WHERE `created_by` = ?"#)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await
+        .context("Failed to delete messages from DB")?;
+    Ok(messages.into_iter().map(super::Message::from).collect())
+}
+
+/// Fetches message `id` if `caller` may read it.
+async fn get_readable_message(
+    conn: &mut MySqlConnection,
+    request: super::GetMessageParams,
+) -> Result<super::Message, Failure> {
+    let super::GetMessageParams { id, caller } = request;
+    let message = get_message(conn, request)
+        .await?
+        .ok_or_else(|| message_not_found(id))?;
+    check_message_access(conn, id, message.channel_id, caller).await?;
+    Ok(message)
+}
+
+/// Publishes `event` once the work of `ctx` is committed.
+fn publish<Ctx>(ctx: &Ctx, event: super::MessageEvent)
+where
+    Ctx: Database + AsRef<super::MessageHub>,
+{
+    let hub: &super::MessageHub = ctx.as_ref();
+    let hub = hub.clone();
+    ctx.after_commit(move || hub.publish(event));
+}
+
+// MARK: impl MessageService
+
+impl<Ctx> super::MessageService<Ctx> for Impl
+where
+    Ctx: Database + AsRef<MySqlPool> + AsRef<super::MessageHub> + AsRef<PresenceHub> + Send + Sync,
+{
+    async fn get_message<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::GetMessageParams,
+    ) -> Result<super::Message, Failure> {
+        let mut conn = ctx.conn().await?;
+        get_readable_message(&mut conn, request).await
+    }
+
+    async fn create_message<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::CreateMessageParams,
+    ) -> Result<super::Message, Failure> {
+        let mut conn = ctx.conn().await?;
+        let channel =
+            check_channel_access(&mut conn, request.channel_id, Some(request.created_by)).await?;
+        if channel.is_archived() {
+            return Err(Failure::reject_failed_precondition("Channel is archived"));
+        }
+        let message = create_message(&mut conn, request).await?;
+        publish(ctx, super::MessageEvent::Created(message.clone()));
+        Ok(message)
+    }
+
+    async fn update_message<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::UpdateMessageParams,
+    ) -> Result<super::Message, Failure> {
+        let get_request = super::GetMessageParams {
+            id: request.id,
+            caller: request.caller,
+        };
+        let id = request.id;
+        let changed = request.text.is_some();
+        let mut tx = ctx.begin().await?;
+        get_readable_message(&mut tx, get_request).await?;
+        let message = update_message(&mut tx, request)
+            .await?
+            .ok_or_else(|| message_not_found(id))?;
+        tx.commit().await?;
+        if changed {
+            publish(ctx, super::MessageEvent::Updated(message.clone()));
+        }
+        Ok(message)
+    }
+
+    async fn delete_message<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::DeleteMessageParams,
+    ) -> Result<super::Message, Failure> {
+        let get_request = super::GetMessageParams {
+            id: request.id,
+            caller: request.caller,
+        };
+        let id = request.id;
+        let mut tx = ctx.begin().await?;
+        get_readable_message(&mut tx, get_request).await?;
+        let message = delete_message(&mut tx, request)
+            .await?
+            .ok_or_else(|| message_not_found(id))?;
+        tx.commit().await?;
+        publish(ctx, super::MessageEvent::Deleted(message.clone()));
+        Ok(message)
+    }
+
+    async fn stream_messages<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::StreamMessagesParams,
+    ) -> Result<super::MessageEventStream, Failure> {
+        let super::StreamMessagesParams {
+            since,
+            channel_ids,
+            caller,
+            keep_present,
+        } = request;
+        let hub: &super::MessageHub = ctx.as_ref();
+        // subscribe before checking access so no event or revocation slips through in between
+        let live = hub.subscribe();
+        // channels created or joined later are not picked up until the client reconnects
+        let mut conn = ctx.conn().await?;
+        let channel_ids = if channel_ids.is_empty() {
+            visible_channel_ids(&mut conn, caller).await?
+        } else {
+            for &id in &channel_ids {
+                check_channel_access(&mut conn, id, caller).await?;
+            }
+            channel_ids
+        };
+        drop(conn);
+        let live = only_channels(live, &channel_ids, caller);
+        // the replay outlives the call, so it reads from the pool
+        let pool: &MySqlPool = ctx.as_ref();
+        let stream = match since {
+            Some(since) => replay_then_live(pool.clone(), since, channel_ids, live),
+            None => live,
+        };
+        let Some(caller) = caller.filter(|_| keep_present) else {
+            return Ok(stream);
+        };
+        let presence: &PresenceHub = ctx.as_ref();
+        Ok(with_presence(stream, presence.connect(caller)))
+    }
+}
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{error::Failure, prelude::Timestamp, user::UserId};
+
+mod hub;
+mod svc;
+
+pub use hub::{Connection as PresenceConnection, Hub as PresenceHub};
+pub use svc::Impl as PresenceServiceImpl;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceStatus {
+    #[default]
+    Offline,
+    Online,
+    /// Connected, but the client reported the user idle.
+    Away,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Presence {
+    pub user_id: UserId,
+    pub status: PresenceStatus,
+    /// When the user was last connected or sent a heartbeat. `None` if not seen in the last
+    /// [`PresenceHub::FORGET_AFTER`] since the server started.
+    pub last_seen_at: Option<Timestamp>,
+}
+
+/// Keeps `caller` present for [`PresenceHub::DEFAULT_TIMEOUT`] even without an open stream.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct HeartbeatParams {
+    pub caller: UserId,
+    pub away: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct GetPresenceParams {
+    pub user_id: UserId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct BatchGetPresenceParams {
+    /// At most [`Self::MAX_IDS`].
+    pub user_ids: Vec<UserId>,
+}
+
+impl BatchGetPresenceParams {
+    pub const MAX_IDS: usize = 100;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct StreamPresenceParams {
+    /// Only changes of these users are streamed. Empty means everyone.
+    pub user_ids: Vec<UserId>,
+}
+
+pub type PresenceStream =
+    std::pin::Pin<Box<dyn futures::Stream<Item = Result<Presence, Failure>> + Send>>;
+
+/// Presence is kept in memory by [`PresenceHub`], so each instance only knows about clients
+/// connected to it. Sharing it across instances means another implementation of this trait
+/// backed by a shared store.
+pub trait PresenceService<Context: ?Sized>: Send + Sync + 'static {
+    fn heartbeat<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: HeartbeatParams,
+    ) -> impl Future<Output = Result<Presence, Failure>> + Send;
+    fn get_presence<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: GetPresenceParams,
+    ) -> impl Future<Output = Result<Presence, Failure>> + Send;
+    /// One entry per requested id in the same order.
+    fn batch_get_presence<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: BatchGetPresenceParams,
+    ) -> impl Future<Output = Result<Vec<Presence>, Failure>> + Send;
+    fn stream_presence<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: StreamPresenceParams,
+    ) -> impl Future<Output = Result<PresenceStream, Failure>> + Send;
+}
+
+pub trait ProvidePresenceService: Send + Sync + 'static {
+    type Context: ?Sized;
+    type PresenceService: PresenceService<Self::Context>;
+
+    fn presence_service(&self) -> &Self::PresenceService;
+    fn context(&self) -> &Self::Context;
+
+    fn heartbeat(
+        &self,
+        params: HeartbeatParams,
+    ) -> impl Future<Output = Result<Presence, Failure>> + Send {
+        let ctx = self.context();
+        self.presence_service().heartbeat(ctx, params)
+    }
+    fn get_presence(
+        &self,
+        params: GetPresenceParams,
+    ) -> impl Future<Output = Result<Presence, Failure>> + Send {
+        let ctx = self.context();
+        self.presence_service().get_presence(ctx, params)
+    }
+    fn batch_get_presence(
+        &self,
+        params: BatchGetPresenceParams,
+    ) -> impl Future<Output = Result<Vec<Presence>, Failure>> + Send {
+        let ctx = self.context();
+        self.presence_service().batch_get_presence(ctx, params)
+    }
+    fn stream_presence(
+        &self,
+        params: StreamPresenceParams,
+    ) -> impl Future<Output = Result<PresenceStream, Failure>> + Send {
+        let ctx = self.context();
+        self.presence_service().stream_presence(ctx, params)
+    }
+}
+
+impl<T> ProvidePresenceService for std::sync::Arc<T>
+where
+    T: ProvidePresenceService,
+{
+    type Context = T::Context;
+    type PresenceService = T::PresenceService;
+
+    fn context(&self) -> &Self::Context {
+        T::context(self)
+    }
+    fn presence_service(&self) -> &Self::PresenceService {
+        T::presence_service(self)
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use tokio::{sync::broadcast, time::Instant};
+use tokio_util::sync::CancellationToken;
+
+use super::{Presence, PresenceStatus};
+use crate::{error::Failure, user::UserId};
+
+#[derive(Debug, Default)]
+struct Entry {
+    /// Open streams of the user.
+    connections: usize,
+    /// The last heartbeat keeps the user present until then.
+    heartbeat_until: Option<Instant>,
+    away: bool,
+    last_seen_at: Option<super::Timestamp>,
+    /// The status subscribers were last told about.
+    published: PresenceStatus,
+}
+
+impl Entry {
+    fn status(&self, now: Instant) -> PresenceStatus {
+        let present = self.connections > 0 || self.heartbeat_until.is_some_and(|t| t > now);
+        match (present, self.away) {
+            (false, _) => PresenceStatus::Offline,
+            (true, true) => PresenceStatus::Away,
+            (true, false) => PresenceStatus::Online,
+        }
+    }
+
+    /// Whether the entry can be dropped, losing nothing but an old `last_seen_at`.
+    fn is_stale(&self, now: Instant, utc_now: super::Timestamp) -> bool {
+        self.status(now) == PresenceStatus::Offline
+            && self.published == PresenceStatus::Offline
+            && self
+                .last_seen_at
+                .is_none_or(|t| utc_now - t > Hub::FORGET_AFTER)
+    }
+
+    fn presence(&self, user_id: UserId, now: Instant) -> Presence {
+        Presence {
+            user_id,
+            status: self.status(now),
+            last_seen_at: self.last_seen_at,
+        }
+    }
+}
+
+/// In-process presence of users, fed by open streams and heartbeats.
+#[derive(Debug, Clone)]
+pub struct Hub {
+    entries: Arc<Mutex<HashMap<UserId, Entry>>>,
+    sender: broadcast::Sender<Presence>,
+    timeout: Duration,
+    shutdown: CancellationToken,
+}
+
+impl Hub {
+    pub const DEFAULT_CAPACITY: usize = 1024;
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+    /// How long an offline user's `last_seen_at` is kept.
+    pub const FORGET_AFTER: chrono::TimeDelta = chrono::TimeDelta::days(1);
+
+    /// A heartbeat keeps its user present for `timeout`. `capacity` is the number of changes a
+    /// subscriber may fall behind before it is disconnected. All streams end once `shutdown` is
+    /// cancelled.
+    pub fn new(capacity: usize, timeout: Duration, shutdown: CancellationToken) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self {
+            entries: Default::default(),
+            sender,
+            timeout,
+            shutdown,
+        }
+    }
+
+    pub fn get(&self, user_id: UserId) -> Presence {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        match entries.get(&user_id) {
+            Some(entry) => entry.presence(user_id, Instant::now()),
+            None => Presence {
+                user_id,
+                status: PresenceStatus::Offline,
+                last_seen_at: None,
+            },
+        }
+    }
+
+    pub fn heartbeat(&self, user_id: UserId, away: bool) -> Presence {
+        let timeout = self.timeout;
+        self.update(user_id, |entry, now| {
+            entry.heartbeat_until = Some(now + timeout);
+            entry.away = away;
+        })
+    }
+
+    /// Keeps the user present until the returned guard is dropped.
+    pub fn connect(&self, user_id: UserId) -> Connection {
+        self.update(user_id, |entry, _| entry.connections += 1);
+        Connection {
+            hub: self.clone(),
+            user_id,
+        }
+    }
+
+    fn disconnect(&self, user_id: UserId) {
+        self.update(user_id, |entry, _| {
+            entry.connections = entry.connections.saturating_sub(1);
+        });
+    }
+
+    /// Applies `f` to the user's entry and tells subscribers if their status changed.
+    fn update(&self, user_id: UserId, f: impl FnOnce(&mut Entry, Instant)) -> Presence {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let entry = entries.entry(user_id).or_default();
+        f(entry, now);
+        entry.last_seen_at = Some(chrono::Utc::now());
+        let presence = entry.presence(user_id, now);
+        self.publish_if_changed(entry, presence);
+        presence
+    }
+
+    fn publish_if_changed(&self, entry: &mut Entry, presence: Presence) {
+        if presence.status == entry.published {
+            return;
+        }
+        entry.published = presence.status;
+        if presence.status == PresenceStatus::Offline {
+            // the next session starts out online
+            entry.away = false;
+        }
+        // no receivers is not an error; the change just has nobody to go to
+        let receivers = self.sender.send(presence).unwrap_or_default();
+        tracing::debug!(receivers, user_id = %presence.user_id.0, status = ?presence.status, "Published a presence change");
+    }
+
+    /// Marks users whose last heartbeat ran out as offline, and forgets users offline for longer
+    /// than [`Self::FORGET_AFTER`], checking every `interval` until shutdown.
+    pub fn spawn_expiry_task(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let hub = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    () = hub.shutdown.cancelled() => break,
+                    _ = ticks.tick() => {}
+                }
+                let now = Instant::now();
+                let mut entries = hub.entries.lock().unwrap_or_else(PoisonError::into_inner);
+                for (&user_id, entry) in entries.iter_mut() {
+                    let presence = entry.presence(user_id, now);
+                    hub.publish_if_changed(entry, presence);
+                }
+                let utc_now = chrono::Utc::now();
+                entries.retain(|_, entry| !entry.is_stale(now, utc_now));
+            }
+        })
+    }
+
+    pub fn subscribe(&self) -> super::PresenceStream {
+        let mut receiver = self.sender.subscribe();
+        let shutdown = self.shutdown.clone();
+        let stream = async_stream::stream! {
+            loop {
+                let received = tokio::select! {
+                    () = shutdown.cancelled() => break,
+                    r = receiver.recv() => r,
+                };
+                match received {
+                    Ok(presence) => yield Ok(presence),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "Presence stream lagged behind");
+                        let message = format!(
+                            "Stream lagged behind by {skipped} changes; reconnect to resume"
+                        );
+                        yield Err(Failure::reject_aborted(message));
+                        break;
+                    }
+                }
+            }
+        };
+        Box::pin(stream)
+    }
+}
+
+/// Keeps a user present while a stream of theirs is open.
+#[derive(Debug)]
+pub struct Connection {
+    hub: Hub,
+    user_id: UserId,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.hub.disconnect(self.user_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(60);
+
+    fn hub() -> Hub {
+        Hub::new(16, TIMEOUT, CancellationToken::new())
+    }
+
+    #[test]
+    fn status_follows_connections_heartbeats_and_away() {
+        let now = Instant::now();
+        let mut entry = Entry::default();
+        assert_eq!(entry.status(now), PresenceStatus::Offline);
+        entry.heartbeat_until = Some(now + TIMEOUT);
+        assert_eq!(entry.status(now), PresenceStatus::Online);
+        entry.away = true;
+        assert_eq!(entry.status(now), PresenceStatus::Away);
+        assert_eq!(entry.status(now + TIMEOUT), PresenceStatus::Offline);
+        entry.connections = 1;
+        assert_eq!(entry.status(now + TIMEOUT), PresenceStatus::Away);
+    }
+
+    #[test]
+    fn publishes_only_status_changes() {
+        let hub = hub();
+        let mut receiver = hub.sender.subscribe();
+        let user_id = UserId(uuid::Uuid::now_v7());
+        let now = Instant::now();
+        let mut entry = Entry {
+            heartbeat_until: Some(now + TIMEOUT),
+            ..Default::default()
+        };
+        for (away, at, expected) in [
+            (false, now, PresenceStatus::Online),
+            (true, now, PresenceStatus::Away),
+            (true, now + TIMEOUT, PresenceStatus::Offline),
+        ] {
+            entry.away = away;
+            let presence = entry.presence(user_id, at);
+            hub.publish_if_changed(&mut entry, presence);
+            // a repeat of the same status is not published again
+            hub.publish_if_changed(&mut entry, presence);
+            assert_eq!(receiver.try_recv().unwrap().status, expected);
+            assert!(receiver.try_recv().is_err());
+        }
+        // going offline resets away for the next session
+        assert!(!entry.away);
+    }
+
+    #[test]
+    fn forgets_only_users_offline_for_long() {
+        let now = Instant::now();
+        let utc_now = chrono::Utc::now();
+        let mut entry = Entry {
+            last_seen_at: Some(utc_now),
+            ..Default::default()
+        };
+        assert!(!entry.is_stale(now, utc_now));
+        assert!(entry.is_stale(now, utc_now + Hub::FORGET_AFTER * 2));
+        entry.connections = 1;
+        entry.published = PresenceStatus::Online;
+        assert!(!entry.is_stale(now, utc_now + Hub::FORGET_AFTER * 2));
+    }
+
+    #[test]
+    fn connection_guard_keeps_the_user_online() {
+        let hub = hub();
+        let user_id = UserId(uuid::Uuid::now_v7());
+        assert_eq!(hub.get(user_id).status, PresenceStatus::Offline);
+        let connection = hub.connect(user_id);
+        assert_eq!(hub.get(user_id).status, PresenceStatus::Online);
+        drop(connection);
+        let presence = hub.get(user_id);
+        assert_eq!(presence.status, PresenceStatus::Offline);
+        assert!(presence.last_seen_at.is_some());
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+
+use crate::error::Failure;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Impl;
+
+// MARK: impl PresenceService
+
+impl<Ctx> super::PresenceService<Ctx> for Impl
+where
+    Ctx: AsRef<super::PresenceHub> + Send + Sync,
+{
+    async fn heartbeat<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::HeartbeatParams,
+    ) -> Result<super::Presence, Failure> {
+        let super::HeartbeatParams { caller, away } = request;
+        let hub: &super::PresenceHub = ctx.as_ref();
+        Ok(hub.heartbeat(caller, away))
+    }
+
+    async fn get_presence<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::GetPresenceParams,
+    ) -> Result<super::Presence, Failure> {
+        let super::GetPresenceParams { user_id } = request;
+        let hub: &super::PresenceHub = ctx.as_ref();
+        Ok(hub.get(user_id))
+    }
+
+    async fn batch_get_presence<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::BatchGetPresenceParams,
+    ) -> Result<Vec<super::Presence>, Failure> {
+        let super::BatchGetPresenceParams { user_ids } = request;
+        if user_ids.len() > super::BatchGetPresenceParams::MAX_IDS {
+            return Err(Failure::reject_invalid_field(
+                "user_ids",
+                format!(
+                    "At most {} ids can be requested at once",
+                    super::BatchGetPresenceParams::MAX_IDS
+                ),
+            ));
+        }
+        let hub: &super::PresenceHub = ctx.as_ref();
+        Ok(user_ids.into_iter().map(|id| hub.get(id)).collect())
+    }
+
+    async fn stream_presence<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::StreamPresenceParams,
+    ) -> Result<super::PresenceStream, Failure> {
+        use futures::StreamExt;
+
+        let super::StreamPresenceParams { user_ids } = request;
+        let hub: &super::PresenceHub = ctx.as_ref();
+        let changes = hub.subscribe();
+        if user_ids.is_empty() {
+            return Ok(changes);
+        }
+        let user_ids: HashSet<_> = user_ids.into_iter().collect();
+        let changes = changes.filter(move |change| {
+            let keep = match change {
+                Ok(presence) => user_ids.contains(&presence.user_id),
+                Err(_) => true,
+            };
+            std::future::ready(keep)
+        });
+        Ok(Box::pin(changes))
+    }
+}
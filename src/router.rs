@@ -1,7 +1,144 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tonic_types::StatusExt;
+
 use crate::error::Failure;
 
+mod auth;
+mod channel;
+mod message;
+mod presence;
 mod user;
 
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RouterConfig {
+    /// Sends the full error chain and backtrace of internal errors to clients.
+    /// Only meant for development; otherwise clients get an opaque error id to report.
+    pub expose_internal_errors: bool,
+}
+
+tokio::task_local! {
+    /// [`RouterConfig::expose_internal_errors`] of the request being handled. Errors are turned
+    /// into statuses deep inside handlers and response streams, so it is scoped around both
+    /// rather than passed along.
+    static EXPOSE_INTERNAL_ERRORS: bool;
+}
+
+async fn scope_config(
+    axum::extract::State(config): axum::extract::State<RouterConfig>,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let expose = config.expose_internal_errors;
+    let res = EXPOSE_INTERNAL_ERRORS.scope(expose, next.run(req)).await;
+    res.map(|inner| axum::body::Body::new(ScopedBody { inner, expose }))
+}
+
+/// Keeps [`EXPOSE_INTERNAL_ERRORS`] set while streamed responses are polled.
+struct ScopedBody {
+    inner: axum::body::Body,
+    expose: bool,
+}
+
+impl http_body::Body for ScopedBody {
+    type Data = bytes::Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        EXPOSE_INTERNAL_ERRORS.sync_scope(this.expose, || Pin::new(&mut this.inner).poll_frame(cx))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// The user a request was made by, stored in the request extensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Caller(pub crate::user::UserId);
+
+/// The API key a request was made with, stored in the request extensions next to [`Caller`].
+/// Absent for session tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallerApiKey(pub crate::auth::ApiKeyId);
+
+impl Caller {
+    fn from_extensions(extensions: &tonic::Extensions) -> Option<crate::user::UserId> {
+        extensions.get::<Self>().map(|Self(id)| *id)
+    }
+
+    fn require(extensions: &tonic::Extensions) -> Result<crate::user::UserId, Failure> {
+        Self::from_extensions(extensions)
+            .ok_or_else(|| Failure::reject_unauthenticated("Authentication required"))
+    }
+}
+
+/// The `update_mask` of an update request. Without a mask, every field set in the request is
+/// updated.
+struct UpdateMask(Option<Vec<String>>);
+
+impl UpdateMask {
+    fn decode(value: Option<prost_types::FieldMask>, known: &[&str]) -> Result<Self, Failure> {
+        let Some(prost_types::FieldMask { paths }) = value.filter(|m| !m.paths.is_empty()) else {
+            return Ok(Self(None));
+        };
+        let unknown: Vec<_> = paths
+            .iter()
+            .filter(|p| !known.contains(&p.as_str()))
+            .collect();
+        if let Some(first) = unknown.first() {
+            let mut reject =
+                crate::error::Reject::bad_request(format!("Unknown update mask path {first:?}"));
+            for path in unknown {
+                reject = reject.with_field_violation(
+                    "update_mask.paths",
+                    format!(
+                        "Unknown field {path:?}, expected one of {}",
+                        known.join(", ")
+                    ),
+                );
+            }
+            return Err(reject.into());
+        }
+        Ok(Self(Some(paths)))
+    }
+
+    /// The new value of `path`, or `None` to leave it unchanged. A masked field that was not set
+    /// gets the default value.
+    fn select<T: Default>(&self, path: &str, value: Option<T>) -> Option<T> {
+        match &self.0 {
+            None => value,
+            Some(paths) if paths.iter().any(|p| p == path) => Some(value.unwrap_or_default()),
+            Some(_) => None,
+        }
+    }
+
+    /// Like [`Self::select`] for fields that cannot be cleared: a masked field must be set.
+    fn select_required<T>(&self, path: &str, value: Option<T>) -> Result<Option<T>, Failure> {
+        match &self.0 {
+            None => Ok(value),
+            Some(paths) if paths.iter().any(|p| p == path) => value.map(Some).ok_or_else(|| {
+                Failure::reject_invalid_field(
+                    path,
+                    format!("{path} cannot be cleared; set it or leave it out of the mask"),
+                )
+            }),
+            Some(_) => Ok(None),
+        }
+    }
+}
+
 struct ErrorStatus(Failure);
 
 impl From<Failure> for ErrorStatus {
@@ -32,36 +169,173 @@ impl From<ErrorStatus> for tonic::Status {
                 RejectKind::BadRequest => tonic::Code::InvalidArgument,
                 RejectKind::Unauthenticated => tonic::Code::Unauthenticated,
                 RejectKind::NotFound => tonic::Code::NotFound,
+                RejectKind::PermissionDenied => tonic::Code::PermissionDenied,
+                RejectKind::Aborted => tonic::Code::Aborted,
+                RejectKind::AlreadyExists => tonic::Code::AlreadyExists,
+                RejectKind::FailedPrecondition => tonic::Code::FailedPrecondition,
+                RejectKind::ResourceExhausted => tonic::Code::ResourceExhausted,
+                RejectKind::Unavailable => tonic::Code::Unavailable,
             }
         }
 
         match value.0 {
             Reject(r) => {
                 tracing::info!(reject = %r);
-                let code = encode_reject_kind(r.kind());
-                let message = r.into_message();
-                tonic::Status::new(code, message)
+                let (kind, message, details) = r.into_parts();
+                let code = encode_reject_kind(kind);
+                if details.is_empty() {
+                    return tonic::Status::new(code, message);
+                }
+                tonic::Status::with_error_details(code, message, encode_reject_details(details))
             }
             Error(e) => {
-                tracing::error!(error = ?e);
-                tonic::Status::internal(format!("{e}"))
+                let error_id = uuid::Uuid::now_v7();
+                tracing::error!(%error_id, error = ?e);
+                let mut details = tonic_types::ErrorDetails::new();
+                details.set_error_info(
+                    "INTERNAL",
+                    "chatting",
+                    [("error_id".to_string(), error_id.to_string())],
+                );
+                // anything outside a request is kept opaque
+                let expose = EXPOSE_INTERNAL_ERRORS.try_with(|e| *e).unwrap_or(false);
+                let message = if expose {
+                    let chain = e.chain().map(|c| c.to_string()).collect::<Vec<_>>();
+                    details.set_debug_info(chain, e.backtrace().to_string());
+                    format!("Internal error {error_id}: {e:#}")
+                } else {
+                    format!("Internal error {error_id}")
+                };
+                tonic::Status::with_error_details(tonic::Code::Internal, message, details)
             }
         }
     }
 }
 
-pub fn make_router<State>(state: State) -> axum::Router
+fn encode_reject_details(value: crate::error::RejectDetails) -> tonic_types::ErrorDetails {
+    let crate::error::RejectDetails {
+        field_violations,
+        resource,
+        retry_after,
+        localized_message,
+    } = value;
+    let mut details = tonic_types::ErrorDetails::new();
+    for v in field_violations {
+        details.add_bad_request_violation(v.field, v.description);
+    }
+    if let Some(r) = resource {
+        details.set_resource_info(r.resource_type, r.resource_name, "", "");
+    }
+    if let Some(delay) = retry_after {
+        details.set_retry_info(Some(delay));
+    }
+    if let Some(m) = localized_message {
+        details.set_localized_message(m.locale, m.message);
+    }
+    details
+}
+
+pub fn make_router<State>(state: State, config: RouterConfig) -> axum::Router
 where
-    State: crate::user::ProvideUserService + Clone,
+    State: crate::auth::ProvideAuthService
+        + crate::authz::ProvideAuthzService
+        + crate::user::ProvideUserService
+        + crate::channel::ProvideChannelService
+        + crate::message::ProvideMessageService
+        + crate::presence::ProvidePresenceService
+        + Clone,
 {
     use tower_http::ServiceBuilderExt;
 
-    let user = user::Service::new(state);
-    let layer = tower::ServiceBuilder::new().trace_for_grpc();
+    let authenticate =
+        axum::middleware::from_fn_with_state(state.clone(), auth::authenticate::<State>);
+    let layer = tower::ServiceBuilder::new()
+        .trace_for_grpc()
+        .layer(axum::middleware::from_fn_with_state(config, scope_config))
+        .layer(authenticate);
+    let auth = auth::Service::new(state.clone());
+    let user = user::Service::new(state.clone());
+    let channel = channel::Service::new(state.clone());
+    let message = message::Service::new(state.clone());
+    let presence = presence::Service::new(state);
     axum::Router::new()
+        .route_service(
+            &format!("/{}/{{*rest}}", auth::SERVICE_NAME),
+            auth::Server::new(auth),
+        )
         .route_service(
             &format!("/{}/{{*rest}}", user::SERVICE_NAME),
             user::Server::new(user),
         )
+        .route_service(
+            &format!("/{}/{{*rest}}", channel::SERVICE_NAME),
+            channel::Server::new(channel),
+        )
+        .route_service(
+            &format!("/{}/{{*rest}}", message::SERVICE_NAME),
+            message::Server::new(message),
+        )
+        .route_service(
+            &format!("/{}/{{*rest}}", presence::SERVICE_NAME),
+            presence::Server::new(presence),
+        )
         .layer(layer)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KNOWN: &[&str] = &["display_name", "bio"];
+
+    fn field_mask(paths: &[&str]) -> Option<prost_types::FieldMask> {
+        let paths = paths.iter().map(|p| p.to_string()).collect();
+        Some(prost_types::FieldMask { paths })
+    }
+
+    #[test]
+    fn update_mask_rejects_unknown_paths() {
+        let Err(Failure::Reject(reject)) =
+            UpdateMask::decode(field_mask(&["bio", "handle"]), KNOWN)
+        else {
+            panic!("unknown path was accepted");
+        };
+        let violations = &reject.details().field_violations;
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].field, "update_mask.paths");
+    }
+
+    #[test]
+    fn update_mask_without_paths_takes_set_fields() {
+        for value in [None, field_mask(&[])] {
+            let mask = UpdateMask::decode(value, KNOWN).unwrap();
+            assert_eq!(
+                mask.select("bio", Some("new".to_string())).as_deref(),
+                Some("new")
+            );
+            assert_eq!(mask.select::<String>("bio", None), None);
+        }
+    }
+
+    #[test]
+    fn update_mask_clears_masked_unset_fields() {
+        let mask = UpdateMask::decode(field_mask(&["bio"]), KNOWN).unwrap();
+        assert_eq!(mask.select::<String>("bio", None).as_deref(), Some(""));
+        // fields left out of the mask are ignored even when set
+        assert_eq!(mask.select("display_name", Some("new".to_string())), None);
+    }
+
+    #[test]
+    fn update_mask_rejects_clearing_required_fields() {
+        let mask = UpdateMask::decode(field_mask(&["display_name"]), KNOWN).unwrap();
+        assert!(
+            mask.select_required::<String>("display_name", None)
+                .is_err()
+        );
+        let selected = mask.select_required("display_name", Some("new".to_string()));
+        assert_eq!(selected.unwrap().as_deref(), Some("new"));
+        let unmasked = UpdateMask::decode(field_mask(&["bio"]), KNOWN).unwrap();
+        let selected = unmasked.select_required("display_name", Some("new".to_string()));
+        assert_eq!(selected.unwrap(), None);
+    }
+}
@@ -0,0 +1,364 @@
+use axum::{extract, middleware::Next, response::Response};
+use schema::auth as generated;
+
+pub use generated::auth_service_server::AuthServiceServer as Server;
+pub use generated::auth_service_server::SERVICE_NAME;
+
+use super::{Caller, CallerApiKey, ErrorStatus, user::encode_user, user::encode_user_id};
+use super::{channel, message, presence, user};
+use crate::{auth as entity, error::Failure, user::UserHandle};
+
+/// Reads a bearer token from the `authorization` metadata, if any.
+fn bearer_token(headers: &http::HeaderMap) -> Result<Option<entity::BearerToken>, Failure> {
+    let Some(value) = headers.get(http::header::AUTHORIZATION) else {
+        return Ok(None);
+    };
+    let value = value
+        .to_str()
+        .map_err(|_| Failure::reject_unauthenticated("Malformed authorization metadata"))?;
+    let token = value
+        .strip_prefix("Bearer ")
+        .or_else(|| value.strip_prefix("bearer "))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .ok_or_else(|| Failure::reject_unauthenticated("Expected a bearer token"))?;
+    Ok(Some(entity::BearerToken(token.to_string())))
+}
+
+/// Resolves the bearer token to a [`Caller`], and a [`CallerApiKey`] for API keys, stored in the
+/// request extensions.
+///
+/// Requests without a token pass through anonymously; each service decides whether it needs
+/// a caller. Requests with an invalid token are rejected here.
+pub async fn authenticate<S>(
+    extract::State(state): extract::State<S>,
+    mut req: extract::Request,
+    next: Next,
+) -> Response
+where
+    S: entity::ProvideAuthService,
+{
+    let token = match bearer_token(req.headers()) {
+        Ok(token) => token,
+        Err(e) => return reject(e),
+    };
+    if let Some(token) = token {
+        match state
+            .authenticate(entity::AuthenticateParams { token })
+            .await
+        {
+            Ok(principal) => {
+                let user_id = principal.user_id();
+                if let entity::Principal::ApiKey { id, scopes, .. } = &principal {
+                    let path = req.uri().path();
+                    let allowed = required_scope(path).is_some_and(|s| scopes.contains(&s));
+                    tracing::info!(api_key_id = %id.0, user_id = %user_id.0, path, allowed, "API key used");
+                    if !allowed {
+                        let message = "API key is not allowed to call this method";
+                        return reject(Failure::reject_permission_denied(message));
+                    }
+                    req.extensions_mut().insert(CallerApiKey(*id));
+                } else {
+                    tracing::debug!(user_id = %user_id.0, "Authenticated");
+                }
+                req.extensions_mut().insert(Caller(user_id));
+            }
+            Err(e) => return reject(e),
+        }
+    }
+    next.run(req).await
+}
+
+/// The scope an API key needs to call the gRPC method at `path`.
+/// `None` means API keys may not call it at all.
+fn required_scope(path: &str) -> Option<entity::ApiKeyScope> {
+    use entity::ApiKeyScope;
+
+    let (service, method) = path.strip_prefix('/')?.split_once('/')?;
+    let scope = match (service, method) {
+        (message::SERVICE_NAME, "GetMessage" | "StreamMessages") => ApiKeyScope::MessagesRead,
+        (message::SERVICE_NAME, "CreateMessage" | "UpdateMessage" | "DeleteMessage") => {
+            ApiKeyScope::MessagesWrite
+        }
+        (channel::SERVICE_NAME, "GetChannel" | "ListChannels") => ApiKeyScope::ChannelsRead,
+        (channel::SERVICE_NAME, _) => ApiKeyScope::ChannelsWrite,
+        (user::SERVICE_NAME, "GetUser" | "BatchGetUsers" | "ListUsers" | "SearchUsers") => {
+            ApiKeyScope::UsersRead
+        }
+        (presence::SERVICE_NAME, "GetPresence" | "BatchGetPresence" | "StreamPresence") => {
+            ApiKeyScope::UsersRead
+        }
+        _ => return None,
+    };
+    Some(scope)
+}
+
+fn reject(failure: Failure) -> Response {
+    let status = tonic::Status::from(ErrorStatus(failure));
+    status.into_http()
+}
+
+fn encode_session(value: entity::Session) -> Result<generated::Session, Failure> {
+    use crate::prelude::convert_timestamp;
+
+    let entity::Session {
+        user_id,
+        access_token: entity::BearerToken(access_token),
+        access_token_expires_at,
+        refresh_token: entity::RefreshToken(refresh_token),
+        refresh_token_expires_at,
+    } = value;
+    let value = generated::Session {
+        user_id: Some(encode_user_id(user_id)),
+        access_token,
+        access_token_expires_at: Some(convert_timestamp(access_token_expires_at)?),
+        refresh_token,
+        refresh_token_expires_at: Some(convert_timestamp(refresh_token_expires_at)?),
+    };
+    Ok(value)
+}
+
+fn encode_api_key_id(value: entity::ApiKeyId) -> schema::id::ApiKeyId {
+    let id = value.0.to_string();
+    schema::id::ApiKeyId { id }
+}
+
+fn decode_api_key_id(
+    value: Option<schema::id::ApiKeyId>,
+    field: &str,
+) -> Result<entity::ApiKeyId, Failure> {
+    let id = value
+        .ok_or_else(|| Failure::reject_invalid_field(field, "API key id must be specified"))?
+        .id
+        .parse()
+        .map_err(|e| Failure::reject_invalid_field(field, format!("Not a UUID: {e}")))?;
+    Ok(entity::ApiKeyId(id))
+}
+
+fn encode_scope(value: entity::ApiKeyScope) -> generated::ApiKeyScope {
+    match value {
+        entity::ApiKeyScope::MessagesRead => generated::ApiKeyScope::MessagesRead,
+        entity::ApiKeyScope::MessagesWrite => generated::ApiKeyScope::MessagesWrite,
+        entity::ApiKeyScope::ChannelsRead => generated::ApiKeyScope::ChannelsRead,
+        entity::ApiKeyScope::ChannelsWrite => generated::ApiKeyScope::ChannelsWrite,
+        entity::ApiKeyScope::UsersRead => generated::ApiKeyScope::UsersRead,
+    }
+}
+
+fn decode_scope(value: i32) -> Result<entity::ApiKeyScope, Failure> {
+    let value = generated::ApiKeyScope::try_from(value).map_err(|e| {
+        Failure::reject_invalid_field("scopes", format!("Invalid API key scope: {e}"))
+    })?;
+    let value = match value {
+        generated::ApiKeyScope::Unspecified => {
+            return Err(Failure::reject_invalid_field(
+                "scopes",
+                "API key scope must be specified",
+            ));
+        }
+        generated::ApiKeyScope::MessagesRead => entity::ApiKeyScope::MessagesRead,
+        generated::ApiKeyScope::MessagesWrite => entity::ApiKeyScope::MessagesWrite,
+        generated::ApiKeyScope::ChannelsRead => entity::ApiKeyScope::ChannelsRead,
+        generated::ApiKeyScope::ChannelsWrite => entity::ApiKeyScope::ChannelsWrite,
+        generated::ApiKeyScope::UsersRead => entity::ApiKeyScope::UsersRead,
+    };
+    Ok(value)
+}
+
+fn encode_api_key(value: entity::ApiKey) -> Result<generated::ApiKey, Failure> {
+    use crate::prelude::convert_timestamp;
+
+    let entity::ApiKey {
+        id,
+        user_id,
+        label: entity::ApiKeyLabel(label),
+        scopes,
+        created_at,
+        last_used_at,
+        revoked_at,
+    } = value;
+    let value = generated::ApiKey {
+        id: Some(encode_api_key_id(id)),
+        user_id: Some(encode_user_id(user_id)),
+        label,
+        scopes: scopes.into_iter().map(|s| encode_scope(s).into()).collect(),
+        created_at: Some(convert_timestamp(created_at)?),
+        last_used_at: last_used_at.map(convert_timestamp).transpose()?,
+        revoked_at: revoked_at.map(convert_timestamp).transpose()?,
+    };
+    Ok(value)
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S>(S);
+
+impl<S> Service<S>
+where
+    S: entity::ProvideAuthService,
+{
+    pub fn new(inner: S) -> Self {
+        Self(inner)
+    }
+}
+
+#[async_trait::async_trait]
+impl<S> generated::auth_service_server::AuthService for Service<S>
+where
+    S: entity::ProvideAuthService,
+{
+    async fn register(
+        &self,
+        req: tonic::Request<generated::RegisterRequest>,
+    ) -> tonic::Result<tonic::Response<generated::RegisterResponse>> {
+        let (_, _, req) = req.into_parts();
+        let generated::RegisterRequest { handle, password } = req;
+        let entity::Registration { user, session } = self
+            .0
+            .register(entity::RegisterParams {
+                handle: UserHandle::new(handle).map_err(ErrorStatus::from)?,
+                password: entity::Password(password),
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let user = encode_user(user).map_err(ErrorStatus)?;
+        let session = encode_session(session).map_err(ErrorStatus)?;
+        let res = generated::RegisterResponse {
+            user: Some(user),
+            session: Some(session),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn login(
+        &self,
+        req: tonic::Request<generated::LoginRequest>,
+    ) -> tonic::Result<tonic::Response<generated::LoginResponse>> {
+        let (_, _, req) = req.into_parts();
+        let generated::LoginRequest { handle, password } = req;
+        let session = self
+            .0
+            .login(entity::LoginParams {
+                handle,
+                password: entity::Password(password),
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let session = encode_session(session).map_err(ErrorStatus)?;
+        let res = generated::LoginResponse {
+            session: Some(session),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn logout(
+        &self,
+        req: tonic::Request<generated::LogoutRequest>,
+    ) -> tonic::Result<tonic::Response<generated::LogoutResponse>> {
+        let (metadata, _, req) = req.into_parts();
+        let generated::LogoutRequest {} = req;
+        let token = bearer_token(&metadata.into_headers())
+            .and_then(|t| {
+                t.ok_or_else(|| Failure::reject_unauthenticated("Authentication required"))
+            })
+            .map_err(ErrorStatus)?;
+        self.0
+            .logout(entity::LogoutParams { token })
+            .await
+            .map_err(ErrorStatus)?;
+        let res = generated::LogoutResponse {};
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn refresh_token(
+        &self,
+        req: tonic::Request<generated::RefreshTokenRequest>,
+    ) -> tonic::Result<tonic::Response<generated::RefreshTokenResponse>> {
+        let (_, _, req) = req.into_parts();
+        let generated::RefreshTokenRequest { refresh_token } = req;
+        let session = self
+            .0
+            .refresh_token(entity::RefreshTokenParams {
+                refresh_token: entity::RefreshToken(refresh_token),
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let session = encode_session(session).map_err(ErrorStatus)?;
+        let res = generated::RefreshTokenResponse {
+            session: Some(session),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn create_api_key(
+        &self,
+        req: tonic::Request<generated::CreateApiKeyRequest>,
+    ) -> tonic::Result<tonic::Response<generated::CreateApiKeyResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::CreateApiKeyRequest { label, scopes } = req;
+        let scopes = scopes
+            .into_iter()
+            .map(decode_scope)
+            .collect::<Result<_, _>>()
+            .map_err(ErrorStatus)?;
+        let entity::CreatedApiKey {
+            api_key,
+            secret: entity::BearerToken(secret),
+        } = self
+            .0
+            .create_api_key(entity::CreateApiKeyParams {
+                label: entity::ApiKeyLabel(label),
+                scopes,
+                caller,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let api_key = encode_api_key(api_key).map_err(ErrorStatus)?;
+        let res = generated::CreateApiKeyResponse {
+            api_key: Some(api_key),
+            secret,
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn list_api_keys(
+        &self,
+        req: tonic::Request<generated::ListApiKeysRequest>,
+    ) -> tonic::Result<tonic::Response<generated::ListApiKeysResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::ListApiKeysRequest {} = req;
+        let api_keys = self
+            .0
+            .list_api_keys(entity::ListApiKeysParams { caller })
+            .await
+            .map_err(ErrorStatus)?;
+        let api_keys = api_keys
+            .into_iter()
+            .map(encode_api_key)
+            .collect::<Result<_, _>>()
+            .map_err(ErrorStatus)?;
+        let res = generated::ListApiKeysResponse { api_keys };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn revoke_api_key(
+        &self,
+        req: tonic::Request<generated::RevokeApiKeyRequest>,
+    ) -> tonic::Result<tonic::Response<generated::RevokeApiKeyResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::RevokeApiKeyRequest { id } = req;
+        let id = decode_api_key_id(id, "id").map_err(ErrorStatus)?;
+        let api_key = self
+            .0
+            .revoke_api_key(entity::RevokeApiKeyParams { id, caller })
+            .await
+            .map_err(ErrorStatus)?;
+        let api_key = encode_api_key(api_key).map_err(ErrorStatus)?;
+        let res = generated::RevokeApiKeyResponse {
+            api_key: Some(api_key),
+        };
+        Ok(tonic::Response::new(res))
+    }
+}
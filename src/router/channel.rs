@@ -0,0 +1,395 @@
+use schema::channel as generated;
+
+pub use generated::channel_service_server::ChannelServiceServer as Server;
+pub use generated::channel_service_server::SERVICE_NAME;
+
+use super::{Caller, ErrorStatus, user::decode_user_id, user::encode_user_id};
+use crate::{authz::ChannelRole, channel as entity, error::Failure};
+
+pub(super) fn encode_channel_id(value: entity::ChannelId) -> schema::id::ChannelId {
+    let id = value.0.to_string();
+    schema::id::ChannelId { id }
+}
+
+pub(super) fn decode_channel_id(
+    value: Option<schema::id::ChannelId>,
+    field: &str,
+) -> Result<entity::ChannelId, Failure> {
+    let id = value
+        .ok_or_else(|| Failure::reject_invalid_field(field, "Channel id must be specified"))?
+        .id
+        .parse()
+        .map_err(|e| Failure::reject_invalid_field(field, format!("Not a UUID: {e}")))?;
+    Ok(entity::ChannelId(id))
+}
+
+fn encode_channel(value: entity::Channel) -> Result<generated::Channel, Failure> {
+    use crate::prelude::convert_timestamp;
+
+    let entity::Channel {
+        id,
+        name,
+        kind,
+        visibility,
+        created_at,
+        updated_at,
+        archived_at,
+    } = value;
+    let value = generated::Channel {
+        id: Some(encode_channel_id(id)),
+        name: name.into(),
+        kind: encode_kind(kind).into(),
+        visibility: encode_visibility(visibility).into(),
+        created_at: Some(convert_timestamp(created_at)?),
+        updated_at: Some(convert_timestamp(updated_at)?),
+        archived_at: archived_at.map(convert_timestamp).transpose()?,
+    };
+    Ok(value)
+}
+
+fn encode_kind(value: entity::ChannelKind) -> generated::ChannelKind {
+    match value {
+        entity::ChannelKind::Group => generated::ChannelKind::Group,
+        entity::ChannelKind::Direct => generated::ChannelKind::Direct,
+    }
+}
+
+fn encode_visibility(value: entity::ChannelVisibility) -> generated::ChannelVisibility {
+    match value {
+        entity::ChannelVisibility::Public => generated::ChannelVisibility::Public,
+        entity::ChannelVisibility::Private => generated::ChannelVisibility::Private,
+    }
+}
+
+fn decode_visibility(value: i32) -> Result<entity::ChannelVisibility, Failure> {
+    let value = generated::ChannelVisibility::try_from(value).map_err(|e| {
+        Failure::reject_invalid_field("visibility", format!("Invalid channel visibility: {e}"))
+    })?;
+    let value = match value {
+        generated::ChannelVisibility::Unspecified | generated::ChannelVisibility::Public => {
+            entity::ChannelVisibility::Public
+        }
+        generated::ChannelVisibility::Private => entity::ChannelVisibility::Private,
+    };
+    Ok(value)
+}
+
+fn encode_role(value: ChannelRole) -> generated::ChannelRole {
+    match value {
+        ChannelRole::Member => generated::ChannelRole::Member,
+        ChannelRole::Moderator => generated::ChannelRole::Moderator,
+        ChannelRole::Owner => generated::ChannelRole::Owner,
+    }
+}
+
+fn decode_role(value: i32) -> Result<ChannelRole, Failure> {
+    let value = generated::ChannelRole::try_from(value)
+        .map_err(|e| Failure::reject_invalid_field("role", format!("Invalid channel role: {e}")))?;
+    let value = match value {
+        generated::ChannelRole::Unspecified => {
+            return Err(Failure::reject_invalid_field(
+                "role",
+                "Channel role must be specified",
+            ));
+        }
+        generated::ChannelRole::Member => ChannelRole::Member,
+        generated::ChannelRole::Moderator => ChannelRole::Moderator,
+        generated::ChannelRole::Owner => ChannelRole::Owner,
+    };
+    Ok(value)
+}
+
+fn encode_channel_member(
+    value: entity::ChannelMember,
+) -> Result<generated::ChannelMember, Failure> {
+    use crate::prelude::convert_timestamp;
+
+    let entity::ChannelMember {
+        channel_id,
+        user_id,
+        role,
+        joined_at,
+    } = value;
+    let value = generated::ChannelMember {
+        channel_id: Some(encode_channel_id(channel_id)),
+        user_id: Some(encode_user_id(user_id)),
+        joined_at: Some(convert_timestamp(joined_at)?),
+        role: encode_role(role).into(),
+    };
+    Ok(value)
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S>(S);
+
+impl<S> Service<S>
+where
+    S: entity::ProvideChannelService,
+{
+    pub fn new(inner: S) -> Self {
+        Self(inner)
+    }
+}
+
+#[async_trait::async_trait]
+impl<S> generated::channel_service_server::ChannelService for Service<S>
+where
+    S: entity::ProvideChannelService,
+{
+    async fn get_channel(
+        &self,
+        req: tonic::Request<generated::GetChannelRequest>,
+    ) -> tonic::Result<tonic::Response<generated::GetChannelResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::from_extensions(&extensions);
+        let generated::GetChannelRequest { id } = req;
+        let id = decode_channel_id(id, "id").map_err(ErrorStatus)?;
+        let channel = self
+            .0
+            .get_channel(entity::GetChannelParams { id, caller })
+            .await
+            .map_err(ErrorStatus)?;
+        let channel = encode_channel(channel).map_err(ErrorStatus)?;
+        let res = generated::GetChannelResponse {
+            channel: Some(channel),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn create_channel(
+        &self,
+        req: tonic::Request<generated::CreateChannelRequest>,
+    ) -> tonic::Result<tonic::Response<generated::CreateChannelResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::CreateChannelRequest { name, visibility } = req;
+        let name = entity::ChannelName::new(name).map_err(ErrorStatus::from)?;
+        let visibility = decode_visibility(visibility).map_err(ErrorStatus)?;
+        let channel = self
+            .0
+            .create_channel(entity::CreateChannelParams {
+                name,
+                visibility,
+                created_by: caller,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let channel = encode_channel(channel).map_err(ErrorStatus)?;
+        let res = generated::CreateChannelResponse {
+            channel: Some(channel),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn rename_channel(
+        &self,
+        req: tonic::Request<generated::RenameChannelRequest>,
+    ) -> tonic::Result<tonic::Response<generated::RenameChannelResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::from_extensions(&extensions);
+        let generated::RenameChannelRequest { id, name } = req;
+        let id = decode_channel_id(id, "id").map_err(ErrorStatus)?;
+        let name = entity::ChannelName::new(name).map_err(ErrorStatus::from)?;
+        let channel = self
+            .0
+            .rename_channel(entity::RenameChannelParams { id, name, caller })
+            .await
+            .map_err(ErrorStatus)?;
+        let channel = encode_channel(channel).map_err(ErrorStatus)?;
+        let res = generated::RenameChannelResponse {
+            channel: Some(channel),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn archive_channel(
+        &self,
+        req: tonic::Request<generated::ArchiveChannelRequest>,
+    ) -> tonic::Result<tonic::Response<generated::ArchiveChannelResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::from_extensions(&extensions);
+        let generated::ArchiveChannelRequest { id } = req;
+        let id = decode_channel_id(id, "id").map_err(ErrorStatus)?;
+        let channel = self
+            .0
+            .archive_channel(entity::ArchiveChannelParams { id, caller })
+            .await
+            .map_err(ErrorStatus)?;
+        let channel = encode_channel(channel).map_err(ErrorStatus)?;
+        let res = generated::ArchiveChannelResponse {
+            channel: Some(channel),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn list_channels(
+        &self,
+        req: tonic::Request<generated::ListChannelsRequest>,
+    ) -> tonic::Result<tonic::Response<generated::ListChannelsResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::from_extensions(&extensions);
+        let generated::ListChannelsRequest { include_archived } = req;
+        let channels = self
+            .0
+            .list_channels(entity::ListChannelsParams {
+                include_archived,
+                caller,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let channels = channels
+            .into_iter()
+            .map(encode_channel)
+            .collect::<Result<_, _>>()
+            .map_err(ErrorStatus)?;
+        let res = generated::ListChannelsResponse { channels };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn join_channel(
+        &self,
+        req: tonic::Request<generated::JoinChannelRequest>,
+    ) -> tonic::Result<tonic::Response<generated::JoinChannelResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::JoinChannelRequest { channel_id } = req;
+        let id = decode_channel_id(channel_id, "channel_id").map_err(ErrorStatus)?;
+        let member = self
+            .0
+            .join_channel(entity::JoinChannelParams { id, caller })
+            .await
+            .map_err(ErrorStatus)?;
+        let member = encode_channel_member(member).map_err(ErrorStatus)?;
+        let res = generated::JoinChannelResponse {
+            member: Some(member),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn leave_channel(
+        &self,
+        req: tonic::Request<generated::LeaveChannelRequest>,
+    ) -> tonic::Result<tonic::Response<generated::LeaveChannelResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::LeaveChannelRequest { channel_id } = req;
+        let id = decode_channel_id(channel_id, "channel_id").map_err(ErrorStatus)?;
+        let member = self
+            .0
+            .leave_channel(entity::LeaveChannelParams { id, caller })
+            .await
+            .map_err(ErrorStatus)?;
+        let member = encode_channel_member(member).map_err(ErrorStatus)?;
+        let res = generated::LeaveChannelResponse {
+            member: Some(member),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn invite_to_channel(
+        &self,
+        req: tonic::Request<generated::InviteToChannelRequest>,
+    ) -> tonic::Result<tonic::Response<generated::InviteToChannelResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::InviteToChannelRequest {
+            channel_id,
+            user_id,
+        } = req;
+        let id = decode_channel_id(channel_id, "channel_id").map_err(ErrorStatus)?;
+        let user_id = decode_user_id(user_id, "user_id").map_err(ErrorStatus)?;
+        let member = self
+            .0
+            .invite_to_channel(entity::InviteToChannelParams {
+                id,
+                user_id,
+                caller,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let member = encode_channel_member(member).map_err(ErrorStatus)?;
+        let res = generated::InviteToChannelResponse {
+            member: Some(member),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn kick_from_channel(
+        &self,
+        req: tonic::Request<generated::KickFromChannelRequest>,
+    ) -> tonic::Result<tonic::Response<generated::KickFromChannelResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::KickFromChannelRequest {
+            channel_id,
+            user_id,
+        } = req;
+        let id = decode_channel_id(channel_id, "channel_id").map_err(ErrorStatus)?;
+        let user_id = decode_user_id(user_id, "user_id").map_err(ErrorStatus)?;
+        let member = self
+            .0
+            .kick_from_channel(entity::KickFromChannelParams {
+                id,
+                user_id,
+                caller,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let member = encode_channel_member(member).map_err(ErrorStatus)?;
+        let res = generated::KickFromChannelResponse {
+            member: Some(member),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn set_channel_member_role(
+        &self,
+        req: tonic::Request<generated::SetChannelMemberRoleRequest>,
+    ) -> tonic::Result<tonic::Response<generated::SetChannelMemberRoleResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::SetChannelMemberRoleRequest {
+            channel_id,
+            user_id,
+            role,
+        } = req;
+        let id = decode_channel_id(channel_id, "channel_id").map_err(ErrorStatus)?;
+        let user_id = decode_user_id(user_id, "user_id").map_err(ErrorStatus)?;
+        let role = decode_role(role).map_err(ErrorStatus)?;
+        let member = self
+            .0
+            .set_channel_member_role(entity::SetChannelMemberRoleParams {
+                id,
+                user_id,
+                role,
+                caller,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let member = encode_channel_member(member).map_err(ErrorStatus)?;
+        let res = generated::SetChannelMemberRoleResponse {
+            member: Some(member),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn open_direct_channel(
+        &self,
+        req: tonic::Request<generated::OpenDirectChannelRequest>,
+    ) -> tonic::Result<tonic::Response<generated::OpenDirectChannelResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::OpenDirectChannelRequest { user_id } = req;
+        let user_id = decode_user_id(user_id, "user_id").map_err(ErrorStatus)?;
+        let channel = self
+            .0
+            .open_direct_channel(entity::OpenDirectChannelParams { user_id, caller })
+            .await
+            .map_err(ErrorStatus)?;
+        let channel = encode_channel(channel).map_err(ErrorStatus)?;
+        let res = generated::OpenDirectChannelResponse {
+            channel: Some(channel),
+        };
+        Ok(tonic::Response::new(res))
+    }
+}
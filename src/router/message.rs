@@ -0,0 +1,251 @@
+use std::pin::Pin;
+
+use futures::Stream;
+use schema::message as generated;
+
+pub use generated::message_service_server::MessageServiceServer as Server;
+pub use generated::message_service_server::SERVICE_NAME;
+
+use super::{
+    Caller, CallerApiKey, ErrorStatus, UpdateMask,
+    channel::{decode_channel_id, encode_channel_id},
+    user::encode_user_id,
+};
+use crate::{
+    authz::{Action, AuthorizeParams, ProvideAuthzService},
+    error::Failure,
+    message as entity,
+};
+
+fn encode_message_id(value: entity::MessageId) -> schema::id::MessageId {
+    let id = value.0.to_string();
+    schema::id::MessageId { id }
+}
+
+fn decode_message_id(
+    value: Option<schema::id::MessageId>,
+    field: &str,
+) -> Result<entity::MessageId, Failure> {
+    let id = value
+        .ok_or_else(|| Failure::reject_invalid_field(field, "Message id must be specified"))?
+        .id
+        .parse()
+        .map_err(|e| Failure::reject_invalid_field(field, format!("Not a UUID: {e}")))?;
+    Ok(entity::MessageId(id))
+}
+
+fn encode_message(value: entity::Message) -> Result<generated::Message, Failure> {
+    use crate::prelude::convert_timestamp;
+
+    let entity::Message {
+        id,
+        channel_id,
+        text: entity::MessageText(text),
+        created_at,
+        updated_at,
+        created_by,
+        version,
+    } = value;
+    let value = generated::Message {
+        id: Some(encode_message_id(id)),
+        text,
+        created_at: Some(convert_timestamp(created_at)?),
+        updated_at: Some(convert_timestamp(updated_at)?),
+        created_by: created_by.map(encode_user_id),
+        channel_id: Some(encode_channel_id(channel_id)),
+        version,
+    };
+    Ok(value)
+}
+
+fn encode_message_event(
+    value: entity::MessageEvent,
+) -> Result<generated::StreamMessageResponse, Failure> {
+    use generated::MessageEventKind as Kind;
+
+    let (kind, message) = match value {
+        entity::MessageEvent::Created(m) => (Kind::Created, m),
+        entity::MessageEvent::Updated(m) => (Kind::Updated, m),
+        entity::MessageEvent::Deleted(m) => (Kind::Deleted, m),
+    };
+    let value = generated::StreamMessageResponse {
+        message: Some(encode_message(message)?),
+        kind: kind.into(),
+    };
+    Ok(value)
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S>(S);
+
+impl<S> Service<S>
+where
+    S: entity::ProvideMessageService,
+{
+    pub fn new(inner: S) -> Self {
+        Self(inner)
+    }
+}
+
+pub type MessageStream =
+    Pin<Box<dyn Stream<Item = tonic::Result<generated::StreamMessageResponse>> + Send>>;
+
+#[async_trait::async_trait]
+impl<S> generated::message_service_server::MessageService for Service<S>
+where
+    S: entity::ProvideMessageService + ProvideAuthzService,
+{
+    async fn get_message(
+        &self,
+        req: tonic::Request<generated::GetMessageRequest>,
+    ) -> tonic::Result<tonic::Response<generated::GetMessageResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::from_extensions(&extensions);
+        let generated::GetMessageRequest { id } = req;
+        let id = decode_message_id(id, "id").map_err(ErrorStatus)?;
+        let message = self
+            .0
+            .get_message(entity::GetMessageParams { id, caller })
+            .await
+            .map_err(ErrorStatus)?;
+        let message = encode_message(message).map_err(ErrorStatus)?;
+        let res = generated::GetMessageResponse {
+            message: Some(message),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn create_message(
+        &self,
+        req: tonic::Request<generated::CreateMessageRequest>,
+    ) -> tonic::Result<tonic::Response<generated::CreateMessageResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::CreateMessageRequest { text, channel_id } = req;
+        let channel_id = decode_channel_id(channel_id, "channel_id").map_err(ErrorStatus)?;
+        let message = self
+            .0
+            .create_message(entity::CreateMessageParams {
+                channel_id,
+                text: entity::MessageText(text),
+                created_by: caller,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let message = encode_message(message).map_err(ErrorStatus)?;
+        let res = generated::CreateMessageResponse {
+            message: Some(message),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn update_message(
+        &self,
+        req: tonic::Request<generated::UpdateMessageRequest>,
+    ) -> tonic::Result<tonic::Response<generated::UpdateMessageResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::from_extensions(&extensions);
+        let generated::UpdateMessageRequest {
+            id,
+            text,
+            update_mask,
+            expected_version,
+        } = req;
+        let id = decode_message_id(id, "id").map_err(ErrorStatus)?;
+        let mask = UpdateMask::decode(update_mask, &["text"]).map_err(ErrorStatus)?;
+        self.0
+            .authorize(AuthorizeParams {
+                action: Action::UpdateMessage(id),
+                caller,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let message = self
+            .0
+            .update_message(entity::UpdateMessageParams {
+                id,
+                text: mask.select("text", Some(text)).map(entity::MessageText),
+                expected_version,
+                caller,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let message = encode_message(message).map_err(ErrorStatus)?;
+        let res = generated::UpdateMessageResponse {
+            message: Some(message),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn delete_message(
+        &self,
+        req: tonic::Request<generated::DeleteMessageRequest>,
+    ) -> tonic::Result<tonic::Response<generated::DeleteMessageResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::from_extensions(&extensions);
+        let generated::DeleteMessageRequest {
+            id,
+            expected_version,
+        } = req;
+        let id = decode_message_id(id, "id").map_err(ErrorStatus)?;
+        self.0
+            .authorize(AuthorizeParams {
+                action: Action::DeleteMessage(id),
+                caller,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let message = self
+            .0
+            .delete_message(entity::DeleteMessageParams {
+                id,
+                expected_version,
+                caller,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let message = encode_message(message).map_err(ErrorStatus)?;
+        let res = generated::DeleteMessageResponse {
+            message: Some(message),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    type StreamMessagesStream = MessageStream;
+
+    async fn stream_messages(
+        &self,
+        req: tonic::Request<generated::StreamMessageRequest>,
+    ) -> tonic::Result<tonic::Response<Self::StreamMessagesStream>> {
+        use futures::StreamExt;
+
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::from_extensions(&extensions);
+        let via_api_key = extensions.get::<CallerApiKey>().is_some();
+        let generated::StreamMessageRequest { since, channel_ids } = req;
+        let since = since
+            .map(|id| decode_message_id(Some(id), "since"))
+            .transpose()
+            .map_err(ErrorStatus)?;
+        let channel_ids = channel_ids
+            .into_iter()
+            .map(|id| decode_channel_id(Some(id), "channel_ids"))
+            .collect::<Result<_, _>>()
+            .map_err(ErrorStatus)?;
+        let events = self
+            .0
+            .stream_messages(entity::StreamMessagesParams {
+                since,
+                channel_ids,
+                caller,
+                keep_present: !via_api_key,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let stream = events.map(|event| {
+            let event = event.and_then(encode_message_event).map_err(ErrorStatus)?;
+            Ok(event)
+        });
+        Ok(tonic::Response::new(Box::pin(stream)))
+    }
+}
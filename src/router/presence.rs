@@ -0,0 +1,151 @@
+use std::pin::Pin;
+
+use futures::Stream;
+use schema::presence as generated;
+
+pub use generated::presence_service_server::PresenceServiceServer as Server;
+pub use generated::presence_service_server::SERVICE_NAME;
+
+use super::{
+    Caller, ErrorStatus,
+    user::{decode_user_id, encode_user_id},
+};
+use crate::{error::Failure, presence as entity};
+
+fn encode_status(value: entity::PresenceStatus) -> generated::PresenceStatus {
+    match value {
+        entity::PresenceStatus::Offline => generated::PresenceStatus::Offline,
+        entity::PresenceStatus::Online => generated::PresenceStatus::Online,
+        entity::PresenceStatus::Away => generated::PresenceStatus::Away,
+    }
+}
+
+fn encode_presence(value: entity::Presence) -> Result<generated::Presence, Failure> {
+    use crate::prelude::convert_timestamp;
+
+    let entity::Presence {
+        user_id,
+        status,
+        last_seen_at,
+    } = value;
+    let value = generated::Presence {
+        user_id: Some(encode_user_id(user_id)),
+        status: encode_status(status).into(),
+        last_seen_at: last_seen_at.map(convert_timestamp).transpose()?,
+    };
+    Ok(value)
+}
+
+fn decode_user_ids(value: Vec<schema::id::UserId>) -> Result<Vec<crate::user::UserId>, Failure> {
+    value
+        .into_iter()
+        .map(|id| decode_user_id(Some(id), "user_ids"))
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S>(S);
+
+impl<S> Service<S>
+where
+    S: entity::ProvidePresenceService,
+{
+    pub fn new(inner: S) -> Self {
+        Self(inner)
+    }
+}
+
+pub type PresenceStream =
+    Pin<Box<dyn Stream<Item = tonic::Result<generated::StreamPresenceResponse>> + Send>>;
+
+#[async_trait::async_trait]
+impl<S> generated::presence_service_server::PresenceService for Service<S>
+where
+    S: entity::ProvidePresenceService,
+{
+    async fn heartbeat(
+        &self,
+        req: tonic::Request<generated::HeartbeatRequest>,
+    ) -> tonic::Result<tonic::Response<generated::HeartbeatResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::HeartbeatRequest { away } = req;
+        let presence = self
+            .0
+            .heartbeat(entity::HeartbeatParams { caller, away })
+            .await
+            .map_err(ErrorStatus)?;
+        let presence = encode_presence(presence).map_err(ErrorStatus)?;
+        let res = generated::HeartbeatResponse {
+            presence: Some(presence),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn get_presence(
+        &self,
+        req: tonic::Request<generated::GetPresenceRequest>,
+    ) -> tonic::Result<tonic::Response<generated::GetPresenceResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::GetPresenceRequest { user_id } = req;
+        let user_id = decode_user_id(user_id, "user_id").map_err(ErrorStatus)?;
+        let presence = self
+            .0
+            .get_presence(entity::GetPresenceParams { user_id })
+            .await
+            .map_err(ErrorStatus)?;
+        let presence = encode_presence(presence).map_err(ErrorStatus)?;
+        let res = generated::GetPresenceResponse {
+            presence: Some(presence),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn batch_get_presence(
+        &self,
+        req: tonic::Request<generated::BatchGetPresenceRequest>,
+    ) -> tonic::Result<tonic::Response<generated::BatchGetPresenceResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::BatchGetPresenceRequest { user_ids } = req;
+        let user_ids = decode_user_ids(user_ids).map_err(ErrorStatus)?;
+        let presences = self
+            .0
+            .batch_get_presence(entity::BatchGetPresenceParams { user_ids })
+            .await
+            .map_err(ErrorStatus)?
+            .into_iter()
+            .map(encode_presence)
+            .collect::<Result<_, _>>()
+            .map_err(ErrorStatus)?;
+        let res = generated::BatchGetPresenceResponse { presences };
+        Ok(tonic::Response::new(res))
+    }
+
+    type StreamPresenceStream = PresenceStream;
+
+    async fn stream_presence(
+        &self,
+        req: tonic::Request<generated::StreamPresenceRequest>,
+    ) -> tonic::Result<tonic::Response<Self::StreamPresenceStream>> {
+        use futures::StreamExt;
+
+        let (_, extensions, req) = req.into_parts();
+        Caller::require(&extensions).map_err(ErrorStatus)?;
+        let generated::StreamPresenceRequest { user_ids } = req;
+        let user_ids = decode_user_ids(user_ids).map_err(ErrorStatus)?;
+        let changes = self
+            .0
+            .stream_presence(entity::StreamPresenceParams { user_ids })
+            .await
+            .map_err(ErrorStatus)?;
+        let stream = changes.map(|change| {
+            let presence = change.and_then(encode_presence).map_err(ErrorStatus)?;
+            Ok(generated::StreamPresenceResponse {
+                presence: Some(presence),
+            })
+        });
+        Ok(tonic::Response::new(Box::pin(stream)))
+    }
+}
@@ -3,32 +3,111 @@ use schema::user as generated;
 pub use generated::user_service_server::SERVICE_NAME;
 pub use generated::user_service_server::UserServiceServer as Server;
 
-use super::ErrorStatus;
-use crate::{error::Failure, user as entity};
+use super::{Caller, ErrorStatus, UpdateMask};
+use crate::{
+    authz::{Action, AuthorizeParams, ProvideAuthzService},
+    error::Failure,
+    user as entity,
+};
 
-fn encode_user_id(value: entity::UserId) -> schema::id::UserId {
+pub(super) fn encode_user_id(value: entity::UserId) -> schema::id::UserId {
     let id = value.0.to_string();
     schema::id::UserId { id }
 }
 
-fn encode_user(value: entity::User) -> Result<generated::User, Failure> {
+pub(super) fn decode_user_id(
+    value: Option<schema::id::UserId>,
+    field: &str,
+) -> Result<entity::UserId, Failure> {
+    let id = value
+        .ok_or_else(|| Failure::reject_invalid_field(field, "User id must be specified"))?
+        .id
+        .parse()
+        .map_err(|e| Failure::reject_invalid_field(field, format!("Not a UUID: {e}")))?;
+    Ok(entity::UserId(id))
+}
+
+pub(super) fn encode_user(value: entity::User) -> Result<generated::User, Failure> {
     use crate::prelude::convert_timestamp;
 
     let entity::User {
         id,
-        name: entity::UserName(name),
+        handle,
+        display_name,
+        bio,
+        avatar_url,
+        version,
         created_at,
         updated_at,
+        deleted_at,
     } = value;
     let value = generated::User {
         id: Some(encode_user_id(id)),
-        name,
+        handle: handle.into(),
         created_at: Some(convert_timestamp(created_at)?),
         updated_at: Some(convert_timestamp(updated_at)?),
+        display_name: display_name.into(),
+        bio: bio.into(),
+        avatar_url: avatar_url.map(String::from).unwrap_or_default(),
+        version,
+        deleted_at: deleted_at.map(convert_timestamp).transpose()?,
     };
     Ok(value)
 }
 
+fn encode_deletion_report(value: entity::DeletionReport) -> generated::UserDeletionReport {
+    let entity::DeletionReport {
+        sessions_revoked,
+        api_keys_revoked,
+        memberships_removed,
+        channels_transferred,
+        channels_archived,
+        messages_anonymized,
+        messages_deleted,
+    } = value;
+    generated::UserDeletionReport {
+        sessions_revoked,
+        api_keys_revoked,
+        memberships_removed,
+        channels_transferred,
+        channels_archived,
+        messages_anonymized,
+        messages_deleted,
+    }
+}
+
+/// Page tokens are the id of the last user of the previous page, kept opaque to clients.
+fn encode_page_token(value: entity::UserId) -> String {
+    use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+
+    URL_SAFE_NO_PAD.encode(value.0.as_bytes())
+}
+
+fn decode_page_token(value: &str) -> Result<entity::UserId, Failure> {
+    use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+
+    let invalid = || Failure::reject_invalid_field("page_token", "Invalid page token");
+    let bytes = URL_SAFE_NO_PAD.decode(value).map_err(|_| invalid())?;
+    let id = uuid::Uuid::from_slice(&bytes).map_err(|_| invalid())?;
+    Ok(entity::UserId(id))
+}
+
+fn encode_search_page_token(value: entity::UserSearchCursor) -> Result<String, Failure> {
+    use anyhow::Context;
+    use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+
+    let json = serde_json::to_vec(&value).context("Failed to encode a page token")?;
+    Ok(URL_SAFE_NO_PAD.encode(json))
+}
+
+fn decode_search_page_token(value: &str) -> Result<entity::UserSearchCursor, Failure> {
+    use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+
+    let invalid = || Failure::reject_invalid_field("page_token", "Invalid page token");
+    let json = URL_SAFE_NO_PAD.decode(value).map_err(|_| invalid())?;
+    serde_json::from_slice(&json).map_err(|_| invalid())
+}
+
 #[derive(Debug, Clone)]
 pub struct Service<S>(S);
 
@@ -44,27 +123,28 @@ where
 #[async_trait::async_trait]
 impl<S> generated::user_service_server::UserService for Service<S>
 where
-    S: entity::ProvideUserService,
+    S: entity::ProvideUserService + ProvideAuthzService,
 {
     async fn get_user(
         &self,
         req: tonic::Request<generated::GetUserRequest>,
     ) -> tonic::Result<tonic::Response<generated::GetUserResponse>> {
-        let (_, _, req) = req.into_parts();
-        let generated::GetUserRequest { id } = req;
-        let id = id
-            .ok_or_else(|| Failure::reject_bad_request("User id must be specified"))
-            .map_err(ErrorStatus)?
-            .id;
-        let id: uuid::Uuid = id
-            .parse()
-            .map_err(|e| Failure::reject_bad_request(format!("Not a UUID: {e}")))
-            .map_err(ErrorStatus)?;
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::from_extensions(&extensions);
+        let generated::GetUserRequest { id, show_deleted } = req;
+        let id = decode_user_id(id, "id").map_err(ErrorStatus)?;
+        if show_deleted {
+            self.0
+                .authorize(AuthorizeParams {
+                    action: Action::ViewDeletedUsers,
+                    caller,
+                })
+                .await
+                .map_err(ErrorStatus)?;
+        }
         let user = self
             .0
-            .get_user(entity::GetUserParams {
-                id: entity::UserId(id),
-            })
+            .get_user(entity::GetUserParams { id, show_deleted })
             .await
             .map_err(ErrorStatus)?;
         let user = encode_user(user).map_err(ErrorStatus)?;
@@ -72,16 +152,145 @@ where
         Ok(tonic::Response::new(res))
     }
 
+    async fn batch_get_users(
+        &self,
+        req: tonic::Request<generated::BatchGetUsersRequest>,
+    ) -> tonic::Result<tonic::Response<generated::BatchGetUsersResponse>> {
+        let (_, _, req) = req.into_parts();
+        let generated::BatchGetUsersRequest { ids } = req;
+        let ids: Vec<_> = ids
+            .into_iter()
+            .map(|id| decode_user_id(Some(id), "ids"))
+            .collect::<Result<_, _>>()
+            .map_err(ErrorStatus)?;
+        let users = self
+            .0
+            .batch_get_users(entity::BatchGetUsersParams { ids: ids.clone() })
+            .await
+            .map_err(ErrorStatus)?;
+        let results = ids
+            .into_iter()
+            .zip(users)
+            .map(|(id, user)| {
+                let result = generated::BatchGetUsersResult {
+                    id: Some(encode_user_id(id)),
+                    user: user.map(encode_user).transpose()?,
+                };
+                Ok(result)
+            })
+            .collect::<Result<_, Failure>>()
+            .map_err(ErrorStatus)?;
+        let res = generated::BatchGetUsersResponse { results };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn list_users(
+        &self,
+        req: tonic::Request<generated::ListUsersRequest>,
+    ) -> tonic::Result<tonic::Response<generated::ListUsersResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::from_extensions(&extensions);
+        let generated::ListUsersRequest {
+            page_size,
+            page_token,
+            handle_prefix,
+            show_deleted,
+        } = req;
+        if show_deleted {
+            self.0
+                .authorize(AuthorizeParams {
+                    action: Action::ViewDeletedUsers,
+                    caller,
+                })
+                .await
+                .map_err(ErrorStatus)?;
+        }
+        let after = (!page_token.is_empty())
+            .then(|| decode_page_token(&page_token))
+            .transpose()
+            .map_err(ErrorStatus)?;
+        let page = self
+            .0
+            .list_users(entity::ListUsersParams {
+                page_size,
+                after,
+                handle_prefix: Some(handle_prefix).filter(|p| !p.is_empty()),
+                show_deleted,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let entity::UserPage { users, next_cursor } = page;
+        let users = users
+            .into_iter()
+            .map(encode_user)
+            .collect::<Result<_, _>>()
+            .map_err(ErrorStatus)?;
+        let res = generated::ListUsersResponse {
+            users,
+            next_page_token: next_cursor.map(encode_page_token).unwrap_or_default(),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn search_users(
+        &self,
+        req: tonic::Request<generated::SearchUsersRequest>,
+    ) -> tonic::Result<tonic::Response<generated::SearchUsersResponse>> {
+        let (_, _, req) = req.into_parts();
+        let generated::SearchUsersRequest {
+            query,
+            page_size,
+            page_token,
+        } = req;
+        let after = (!page_token.is_empty())
+            .then(|| decode_search_page_token(&page_token))
+            .transpose()
+            .map_err(ErrorStatus)?;
+        let page = self
+            .0
+            .search_users(entity::SearchUsersParams {
+                query,
+                page_size,
+                after,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let entity::UserSearchPage { users, next_cursor } = page;
+        let users = users
+            .into_iter()
+            .map(encode_user)
+            .collect::<Result<_, _>>()
+            .map_err(ErrorStatus)?;
+        let next_page_token = next_cursor
+            .map(encode_search_page_token)
+            .transpose()
+            .map_err(ErrorStatus)?
+            .unwrap_or_default();
+        let res = generated::SearchUsersResponse {
+            users,
+            next_page_token,
+        };
+        Ok(tonic::Response::new(res))
+    }
+
     async fn create_user(
         &self,
         req: tonic::Request<generated::CreateUserRequest>,
     ) -> tonic::Result<tonic::Response<generated::CreateUserResponse>> {
         let (_, _, req) = req.into_parts();
-        let generated::CreateUserRequest { name } = req;
+        let generated::CreateUserRequest {
+            handle,
+            display_name,
+        } = req;
+        let display_name = display_name
+            .map(entity::DisplayName::new)
+            .transpose()
+            .map_err(ErrorStatus::from)?;
         let user = self
             .0
             .create_user(entity::CreateUserParams {
-                name: entity::UserName(name),
+                handle: entity::UserHandle::new(handle).map_err(ErrorStatus::from)?,
+                display_name,
             })
             .await
             .map_err(ErrorStatus)?;
@@ -94,20 +303,56 @@ where
         &self,
         req: tonic::Request<generated::UpdateUserRequest>,
     ) -> tonic::Result<tonic::Response<generated::UpdateUserResponse>> {
-        let (_, _, req) = req.into_parts();
-        let generated::UpdateUserRequest { id, name } = req;
-        let id = id
-            .ok_or_else(|| Failure::reject_bad_request("User id must be specified"))
-            .map_err(ErrorStatus)?
-            .id
-            .parse()
-            .map_err(|e| Failure::reject_bad_request(format!("Not a UUID: {e}")))
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::from_extensions(&extensions);
+        let generated::UpdateUserRequest {
+            id,
+            display_name,
+            bio,
+            avatar_url,
+            update_mask,
+            expected_version,
+        } = req;
+        let mask = UpdateMask::decode(update_mask, &["display_name", "bio", "avatar_url"])
+            .map_err(ErrorStatus)?;
+        let id = decode_user_id(id, "id").map_err(ErrorStatus)?;
+        self.0
+            .authorize(AuthorizeParams {
+                action: Action::UpdateUser(id),
+                caller,
+            })
+            .await
             .map_err(ErrorStatus)?;
+        let display_name = mask
+            .select_required("display_name", display_name)
+            .map_err(ErrorStatus)?
+            .map(entity::DisplayName::new)
+            .transpose()
+            .map_err(ErrorStatus::from)?;
+        let bio = mask
+            .select("bio", bio)
+            .map(entity::Bio::new)
+            .transpose()
+            .map_err(ErrorStatus::from)?;
+        let avatar_url = mask
+            .select("avatar_url", avatar_url)
+            .map(|url| {
+                if url.is_empty() {
+                    Ok(None)
+                } else {
+                    entity::AvatarUrl::new(url).map(Some)
+                }
+            })
+            .transpose()
+            .map_err(ErrorStatus::from)?;
         let user = self
             .0
             .update_user(entity::UpdateUserParams {
-                id: entity::UserId(id),
-                name: entity::UserName(name),
+                id,
+                display_name,
+                bio,
+                avatar_url,
+                expected_version,
             })
             .await
             .map_err(ErrorStatus)?;
@@ -120,24 +365,58 @@ where
         &self,
         req: tonic::Request<generated::DeleteUserRequest>,
     ) -> tonic::Result<tonic::Response<generated::DeleteUserResponse>> {
-        let (_, _, req) = req.into_parts();
-        let generated::DeleteUserRequest { id } = req;
-        let id = id
-            .ok_or_else(|| Failure::reject_bad_request("User id must be specified"))
-            .map_err(ErrorStatus)?
-            .id
-            .parse()
-            .map_err(|e| Failure::reject_bad_request(format!("Not a UUID: {e}")))
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::from_extensions(&extensions);
+        let generated::DeleteUserRequest {
+            id,
+            expected_version,
+        } = req;
+        let id = decode_user_id(id, "id").map_err(ErrorStatus)?;
+        self.0
+            .authorize(AuthorizeParams {
+                action: Action::DeleteUser(id),
+                caller,
+            })
+            .await
             .map_err(ErrorStatus)?;
-        let user = self
+        let entity::UserDeletion { user, report } = self
             .0
             .delete_user(entity::DeleteUserParams {
-                id: entity::UserId(id),
+                id,
+                expected_version,
+            })
+            .await
+            .map_err(ErrorStatus)?;
+        let user = encode_user(user).map_err(ErrorStatus)?;
+        let res = generated::DeleteUserResponse {
+            user: Some(user),
+            report: Some(encode_deletion_report(report)),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    async fn restore_user(
+        &self,
+        req: tonic::Request<generated::RestoreUserRequest>,
+    ) -> tonic::Result<tonic::Response<generated::RestoreUserResponse>> {
+        let (_, extensions, req) = req.into_parts();
+        let caller = Caller::from_extensions(&extensions);
+        let generated::RestoreUserRequest { id } = req;
+        let id = decode_user_id(id, "id").map_err(ErrorStatus)?;
+        self.0
+            .authorize(AuthorizeParams {
+                action: Action::RestoreUser(id),
+                caller,
             })
             .await
             .map_err(ErrorStatus)?;
+        let user = self
+            .0
+            .restore_user(entity::RestoreUserParams { id })
+            .await
+            .map_err(ErrorStatus)?;
         let user = encode_user(user).map_err(ErrorStatus)?;
-        let res = generated::DeleteUserResponse { user: Some(user) };
+        let res = generated::RestoreUserResponse { user: Some(user) };
         Ok(tonic::Response::new(res))
     }
 }
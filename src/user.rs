@@ -1,46 +1,461 @@
 use serde::{Deserialize, Serialize};
 
-use crate::{error::Failure, prelude::Timestamp};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{
+    error::{Failure, Reject},
+    prelude::Timestamp,
+};
 
 mod svc;
 
-pub use svc::Impl as UserServiceImpl;
+pub(crate) use svc::create_user;
+pub use svc::{Impl as UserServiceImpl, spawn_purge_task};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
 #[serde(transparent)]
 pub struct UserId(pub uuid::Uuid);
 
+/// The `@handle` users are mentioned by; it never changes once taken.
+///
+/// NFKC-normalized, 3 to 32 letters, digits, `_`, `-` and `.`, starting and ending with a
+/// letter or digit. Unique among users, compared case-insensitively by the DB.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
-#[serde(transparent)]
-pub struct UserName(pub String);
+#[serde(try_from = "String", into = "String")]
+pub struct UserHandle(String);
+
+impl UserHandle {
+    pub const MIN_CHARS: usize = 3;
+    pub const MAX_CHARS: usize = 32;
+    const RESERVED: &[&str] = &[
+        "admin",
+        "administrator",
+        "anonymous",
+        "chatting",
+        "me",
+        "moderator",
+        "null",
+        "root",
+        "support",
+        "system",
+    ];
+
+    pub fn new(value: impl Into<String>) -> Result<Self, Reject> {
+        let invalid = |description: String| {
+            Reject::bad_request(description.clone()).with_field_violation("handle", description)
+        };
+
+        let value: String = value.into().nfkc().collect();
+        let len = value.chars().count();
+        if !(Self::MIN_CHARS..=Self::MAX_CHARS).contains(&len) {
+            let (min, max) = (Self::MIN_CHARS, Self::MAX_CHARS);
+            return Err(invalid(format!("Handle must be {min} to {max} characters")));
+        }
+        if let Some(c) = value
+            .chars()
+            .find(|c| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')))
+        {
+            return Err(invalid(format!("Handle must not contain {c:?}")));
+        }
+        let starts_and_ends_alphanumeric = value.chars().next().is_some_and(char::is_alphanumeric)
+            && value.chars().last().is_some_and(char::is_alphanumeric);
+        if !starts_and_ends_alphanumeric {
+            return Err(invalid(
+                "Handle must start and end with a letter or digit".to_string(),
+            ));
+        }
+        if Self::RESERVED.contains(&value.to_lowercase().as_str()) {
+            return Err(invalid(format!("Handle {value} is reserved")));
+        }
+        Ok(Self(value))
+    }
+
+    /// Skips validation for handles already stored, which may predate the current rules.
+    pub(crate) fn from_stored(value: String) -> Self {
+        Self(value)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for UserHandle {
+    type Error = Reject;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl From<UserHandle> for String {
+    fn from(value: UserHandle) -> Self {
+        value.0
+    }
+}
+
+/// Shown instead of the handle and free to change. 1 to 64 characters without control
+/// characters; surrounding whitespace is dropped.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DisplayName(String);
+
+impl DisplayName {
+    pub const MAX_CHARS: usize = 64;
+
+    pub fn new(value: impl Into<String>) -> Result<Self, Reject> {
+        let invalid = |description: String| {
+            Reject::bad_request(description.clone())
+                .with_field_violation("display_name", description)
+        };
+
+        let value: String = value.into().nfkc().collect();
+        let value = value.trim();
+        let len = value.chars().count();
+        if !(1..=Self::MAX_CHARS).contains(&len) {
+            let max = Self::MAX_CHARS;
+            return Err(invalid(format!(
+                "Display name must be 1 to {max} characters"
+            )));
+        }
+        if value.chars().any(char::is_control) {
+            return Err(invalid(
+                "Display name must not contain control characters".to_string(),
+            ));
+        }
+        Ok(Self(value.to_string()))
+    }
+
+    pub(crate) fn from_stored(value: String) -> Self {
+        Self(value)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<UserHandle> for DisplayName {
+    fn from(value: UserHandle) -> Self {
+        Self(value.0)
+    }
+}
+
+impl TryFrom<String> for DisplayName {
+    type Error = Reject;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl From<DisplayName> for String {
+    fn from(value: DisplayName) -> Self {
+        value.0
+    }
+}
+
+/// Free-form self introduction of at most 500 characters; may be empty.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Bio(String);
+
+impl Bio {
+    pub const MAX_CHARS: usize = 500;
+
+    pub fn new(value: impl Into<String>) -> Result<Self, Reject> {
+        let value = value.into();
+        if value.chars().count() > Self::MAX_CHARS {
+            let description = format!("Bio must be at most {} characters", Self::MAX_CHARS);
+            return Err(
+                Reject::bad_request(description.clone()).with_field_violation("bio", description)
+            );
+        }
+        Ok(Self(value))
+    }
+
+    pub(crate) fn from_stored(value: String) -> Self {
+        Self(value)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for Bio {
+    type Error = Reject;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl From<Bio> for String {
+    fn from(value: Bio) -> Self {
+        value.0
+    }
+}
+
+/// An `https` URL of the avatar image. Images are hosted elsewhere; only the reference is kept.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AvatarUrl(String);
+
+impl AvatarUrl {
+    pub const MAX_LEN: usize = 2048;
+
+    pub fn new(value: impl Into<String>) -> Result<Self, Reject> {
+        let invalid = |description: &str| {
+            Reject::bad_request(description).with_field_violation("avatar_url", description)
+        };
+
+        let value = value.into();
+        if value.len() > Self::MAX_LEN {
+            return Err(invalid("Avatar URL is too long"));
+        }
+        let uri: http::Uri = value
+            .parse()
+            .map_err(|_| invalid("Avatar URL is not a valid URL"))?;
+        if uri.scheme() != Some(&http::uri::Scheme::HTTPS) || uri.host().is_none() {
+            return Err(invalid("Avatar URL must be an https URL"));
+        }
+        Ok(Self(value))
+    }
+
+    pub(crate) fn from_stored(value: String) -> Self {
+        Self(value)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for AvatarUrl {
+    type Error = Reject;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl From<AvatarUrl> for String {
+    fn from(value: AvatarUrl) -> Self {
+        value.0
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct User {
     pub id: UserId,
-    pub name: UserName,
+    pub handle: UserHandle,
+    pub display_name: DisplayName,
+    pub bio: Bio,
+    pub avatar_url: Option<AvatarUrl>,
+    /// Incremented on every update.
+    pub version: u64,
     pub created_at: Timestamp,
     pub updated_at: Timestamp,
+    /// Set once the user is deleted; the row is purged after the retention period.
+    pub deleted_at: Option<Timestamp>,
+}
+
+impl User {
+    /// Names users in reject details.
+    pub const RESOURCE_TYPE: &str = "chatting.user.User";
+    pub const DELETED_DISPLAY_NAME: &str = "Deleted user";
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// What others see of a deleted user, e.g. as the author of a message.
+    pub fn redacted(self) -> Self {
+        Self {
+            display_name: DisplayName(Self::DELETED_DISPLAY_NAME.to_owned()),
+            bio: Bio::default(),
+            avatar_url: None,
+            ..self
+        }
+    }
+}
+
+pub(crate) fn user_not_found(UserId(id): UserId) -> Failure {
+    Failure::reject_resource_not_found("User not found", User::RESOURCE_TYPE, id.to_string())
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct GetUserParams {
     pub id: UserId,
+    /// Deleted users are not found unless this is set.
+    pub show_deleted: bool,
+}
+
+/// Deleted users are included, [redacted](User::redacted), so that what they wrote can still be
+/// attributed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct BatchGetUsersParams {
+    /// At most [`Self::MAX_IDS`]; duplicates are allowed.
+    pub ids: Vec<UserId>,
+}
+
+impl BatchGetUsersParams {
+    pub const MAX_IDS: usize = 100;
+}
+
+/// Users ordered by creation time, oldest first, or by handle when filtered by
+/// [`Self::handle_prefix`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct ListUsersParams {
+    /// 0 means [`Self::DEFAULT_PAGE_SIZE`]; larger values are capped at [`Self::MAX_PAGE_SIZE`].
+    pub page_size: u32,
+    /// Continues after this user, taken from [`UserPage::next_cursor`].
+    pub after: Option<UserId>,
+    /// Only users whose handle starts with this, NFKC-normalized like handles and compared
+    /// case-insensitively. Since names were split into handles and display names, this matches
+    /// the handle, which is unique and indexed; [`UserService::search_users`] also matches
+    /// display names.
+    pub handle_prefix: Option<String>,
+    /// Deleted users are left out unless this is set.
+    pub show_deleted: bool,
+}
+
+impl ListUsersParams {
+    pub const DEFAULT_PAGE_SIZE: u32 = 50;
+    pub const MAX_PAGE_SIZE: u32 = 200;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct UserPage {
+    pub users: Vec<User>,
+    /// `None` on the last page.
+    pub next_cursor: Option<UserId>,
+}
+
+/// Users whose handle or display name contains the query, ignoring case and accents, deleted
+/// users left out. Best matches
+/// come first: the exact handle, then handle prefixes, display name prefixes, word prefixes within
+/// the display name, and finally any substring.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct SearchUsersParams {
+    pub query: String,
+    /// 0 means [`ListUsersParams::DEFAULT_PAGE_SIZE`]; larger values are capped at
+    /// [`ListUsersParams::MAX_PAGE_SIZE`].
+    pub page_size: u32,
+    /// Continues after this position, taken from [`UserSearchPage::next_cursor`] of a search with
+    /// the same query.
+    pub after: Option<UserSearchCursor>,
+}
+
+impl SearchUsersParams {
+    pub const MAX_QUERY_CHARS: usize = 64;
+}
+
+/// Position in search results: how well the last user matched, then their handle.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct UserSearchCursor {
+    pub rank: u8,
+    /// Not a [`UserHandle`], which would reject handles that predate the current rules when the
+    /// cursor is decoded.
+    pub handle: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct UserSearchPage {
+    pub users: Vec<User>,
+    /// `None` on the last page.
+    pub next_cursor: Option<UserSearchCursor>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct CreateUserParams {
-    pub name: UserName,
+    pub handle: UserHandle,
+    /// Defaults to the handle.
+    pub display_name: Option<DisplayName>,
 }
 
+/// Fields left `None` are kept as they are. The handle cannot be changed.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct UpdateUserParams {
     pub id: UserId,
-    pub name: UserName,
+    pub display_name: Option<DisplayName>,
+    pub bio: Option<Bio>,
+    /// `Some(None)` removes the avatar.
+    pub avatar_url: Option<Option<AvatarUrl>>,
+    /// Rejects with [`RejectKind::Aborted`](crate::error::RejectKind::Aborted) unless the user
+    /// is still at this version.
+    pub expected_version: Option<u64>,
+}
+
+/// What happens to messages of a deleted user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessagePolicy {
+    /// They stay attributed to the user, shown as deleted, until the user is purged.
+    #[default]
+    Keep,
+    /// They lose their author right away.
+    Anonymize,
+    /// They are removed along with the user.
+    Delete,
+}
+
+impl std::str::FromStr for MessagePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "anonymize" => Ok(Self::Anonymize),
+            "delete" => Ok(Self::Delete),
+            _ => anyhow::bail!("Unknown message policy {s:?}, expected keep, anonymize or delete"),
+        }
+    }
+}
+
+/// How a deployment cleans up after [`UserService::delete_user`]. Sessions and API keys are
+/// always revoked, and group channel memberships dropped with ownership handed over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+pub struct DeletionPolicy {
+    pub messages: MessagePolicy,
 }
 
+/// Marks the user deleted and cleans up after them according to the [`DeletionPolicy`]. The user
+/// can be restored until they are purged.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct DeleteUserParams {
     pub id: UserId,
+    /// Rejects with [`RejectKind::Aborted`](crate::error::RejectKind::Aborted) unless the user
+    /// is still at this version.
+    pub expected_version: Option<u64>,
+}
+
+/// What deleting an user affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+pub struct DeletionReport {
+    pub sessions_revoked: u64,
+    pub api_keys_revoked: u64,
+    pub memberships_removed: u64,
+    /// Channels whose ownership passed to another member.
+    pub channels_transferred: u64,
+    /// Channels archived because nobody was left to own them.
+    pub channels_archived: u64,
+    pub messages_anonymized: u64,
+    pub messages_deleted: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct UserDeletion {
+    pub user: User,
+    pub report: DeletionReport,
+}
+
+/// Revoked credentials, channel memberships and anonymized or deleted messages are not brought
+/// back.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct RestoreUserParams {
+    pub id: UserId,
 }
 
 pub trait UserService<Context: ?Sized>: Send + Sync + 'static {
@@ -49,6 +464,22 @@ pub trait UserService<Context: ?Sized>: Send + Sync + 'static {
         ctx: &'a Context,
         params: GetUserParams,
     ) -> impl Future<Output = Result<User, Failure>> + Send;
+    /// One entry per requested id in the same order, `None` where no user has that id.
+    fn batch_get_users<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: BatchGetUsersParams,
+    ) -> impl Future<Output = Result<Vec<Option<User>>, Failure>> + Send;
+    fn list_users<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: ListUsersParams,
+    ) -> impl Future<Output = Result<UserPage, Failure>> + Send;
+    fn search_users<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: SearchUsersParams,
+    ) -> impl Future<Output = Result<UserSearchPage, Failure>> + Send;
     fn create_user<'a>(
         &'a self,
         ctx: &'a Context,
@@ -63,6 +494,11 @@ pub trait UserService<Context: ?Sized>: Send + Sync + 'static {
         &'a self,
         ctx: &'a Context,
         params: DeleteUserParams,
+    ) -> impl Future<Output = Result<UserDeletion, Failure>> + Send;
+    fn restore_user<'a>(
+        &'a self,
+        ctx: &'a Context,
+        params: RestoreUserParams,
     ) -> impl Future<Output = Result<User, Failure>> + Send;
 }
 
@@ -80,6 +516,27 @@ pub trait ProvideUserService: Send + Sync + 'static {
         let ctx = self.context();
         self.user_service().get_user(ctx, params)
     }
+    fn batch_get_users(
+        &self,
+        params: BatchGetUsersParams,
+    ) -> impl Future<Output = Result<Vec<Option<User>>, Failure>> + Send {
+        let ctx = self.context();
+        self.user_service().batch_get_users(ctx, params)
+    }
+    fn list_users(
+        &self,
+        params: ListUsersParams,
+    ) -> impl Future<Output = Result<UserPage, Failure>> + Send {
+        let ctx = self.context();
+        self.user_service().list_users(ctx, params)
+    }
+    fn search_users(
+        &self,
+        params: SearchUsersParams,
+    ) -> impl Future<Output = Result<UserSearchPage, Failure>> + Send {
+        let ctx = self.context();
+        self.user_service().search_users(ctx, params)
+    }
     fn create_user(
         &self,
         params: CreateUserParams,
@@ -97,10 +554,17 @@ pub trait ProvideUserService: Send + Sync + 'static {
     fn delete_user(
         &self,
         params: DeleteUserParams,
-    ) -> impl Future<Output = Result<User, Failure>> + Send {
+    ) -> impl Future<Output = Result<UserDeletion, Failure>> + Send {
         let ctx = self.context();
         self.user_service().delete_user(ctx, params)
     }
+    fn restore_user(
+        &self,
+        params: RestoreUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send {
+        let ctx = self.context();
+        self.user_service().restore_user(ctx, params)
+    }
 }
 
 impl<T> ProvideUserService for std::sync::Arc<T>
@@ -117,3 +581,77 @@ where
         T::user_service(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn violated_field(reject: &Reject) -> &str {
+        &reject.details().field_violations[0].field
+    }
+
+    #[test]
+    fn handle_length_is_bounded() {
+        assert!(UserHandle::new("ab").is_err());
+        assert!(UserHandle::new("abc").is_ok());
+        assert!(UserHandle::new("a".repeat(UserHandle::MAX_CHARS)).is_ok());
+        let reject = UserHandle::new("a".repeat(UserHandle::MAX_CHARS + 1)).unwrap_err();
+        assert_eq!(violated_field(&reject), "handle");
+    }
+
+    #[test]
+    fn handle_is_nfkc_normalized() {
+        // fullwidth letters fold to ASCII
+        let handle = UserHandle::new("ａｌｉｃｅ").unwrap();
+        assert_eq!(handle.as_str(), "alice");
+    }
+
+    #[test]
+    fn handle_rejects_bad_characters() {
+        assert!(UserHandle::new("al ice").is_err());
+        assert!(UserHandle::new("alice@example").is_err());
+        assert!(UserHandle::new("_alice").is_err());
+        assert!(UserHandle::new("alice.").is_err());
+        assert!(UserHandle::new("al_ice-b.c").is_ok());
+    }
+
+    #[test]
+    fn handle_rejects_reserved_names_in_any_case() {
+        assert!(UserHandle::new("admin").is_err());
+        assert!(UserHandle::new("Root").is_err());
+        assert!(UserHandle::new("admins").is_ok());
+    }
+
+    #[test]
+    fn display_name_is_trimmed_and_bounded() {
+        assert_eq!(DisplayName::new("  Alice  ").unwrap().as_str(), "Alice");
+        assert!(DisplayName::new("   ").is_err());
+        assert!(DisplayName::new("").is_err());
+        assert!(DisplayName::new("a".repeat(DisplayName::MAX_CHARS)).is_ok());
+        let reject = DisplayName::new("a".repeat(DisplayName::MAX_CHARS + 1)).unwrap_err();
+        assert_eq!(violated_field(&reject), "display_name");
+    }
+
+    #[test]
+    fn display_name_is_nfkc_normalized_and_rejects_control_characters() {
+        assert_eq!(DisplayName::new("ｱﾘｽ").unwrap().as_str(), "アリス");
+        assert!(DisplayName::new("Al\u{7}ice").is_err());
+    }
+
+    #[test]
+    fn avatar_url_must_be_https() {
+        assert!(AvatarUrl::new("https://example.com/a.png").is_ok());
+        assert!(AvatarUrl::new("http://example.com/a.png").is_err());
+        assert!(AvatarUrl::new("https:///a.png").is_err());
+        let reject = AvatarUrl::new("not a url").unwrap_err();
+        assert_eq!(violated_field(&reject), "avatar_url");
+    }
+
+    #[test]
+    fn avatar_url_length_is_bounded() {
+        let prefix = "https://example.com/";
+        let fits = format!("{prefix}{}", "a".repeat(AvatarUrl::MAX_LEN - prefix.len()));
+        assert!(AvatarUrl::new(fits.clone()).is_ok());
+        assert!(AvatarUrl::new(format!("{fits}a")).is_err());
+    }
+}
@@ -1,144 +1,649 @@
+use std::collections::HashMap;
+
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, MySqlPool};
+use sqlx::{FromRow, MySql, MySqlConnection, MySqlPool, QueryBuilder};
+use tokio_util::sync::CancellationToken;
+use unicode_normalization::UnicodeNormalization;
 use uuid::Uuid;
 
-use crate::error::Failure;
+use crate::{
+    auth::RevocationList,
+    db::{Database, Tx},
+    error::{Failure, Reject, is_unique_violation, lock_failed},
+    message::{MessageEvent, MessageHub},
+    user::user_not_found,
+};
 
 #[derive(Debug, Clone, Copy, Default)]
-pub struct Impl;
+pub struct Impl {
+    deletion_policy: super::DeletionPolicy,
+}
+
+impl Impl {
+    pub fn new(deletion_policy: super::DeletionPolicy) -> Self {
+        Self { deletion_policy }
+    }
+}
 
 // MARK: helper types
 
 #[derive(Debug, Clone, Hash, Deserialize, Serialize, FromRow)]
 struct UserRow {
     pub id: Uuid,
-    pub name: String,
+    pub handle: String,
+    pub display_name: String,
+    pub bio: String,
+    pub avatar_url: Option<String>,
+    pub version: u64,
     pub created_at: super::Timestamp,
     pub updated_at: super::Timestamp,
+    pub deleted_at: Option<super::Timestamp>,
 }
 
 impl From<UserRow> for super::User {
     fn from(value: UserRow) -> Self {
         Self {
             id: super::UserId(value.id),
-            name: super::UserName(value.name),
+            handle: super::UserHandle::from_stored(value.handle),
+            display_name: super::DisplayName::from_stored(value.display_name),
+            bio: super::Bio::from_stored(value.bio),
+            avatar_url: value.avatar_url.map(super::AvatarUrl::from_stored),
+            version: value.version,
             created_at: value.created_at,
             updated_at: value.updated_at,
+            deleted_at: value.deleted_at,
         }
     }
 }
 
 // MARK: helper fns
 
+fn handle_taken(e: sqlx::Error, context: &'static str) -> Failure {
+    if is_unique_violation(&e) {
+        let description = "Handle is already taken";
+        return Reject::already_exists(description)
+            .with_field_violation("handle", description)
+            .into();
+    }
+    anyhow::Error::new(e).context(context).into()
+}
+
+fn version_mismatch(id: Uuid) -> Failure {
+    Reject::aborted("User was modified concurrently")
+        .with_resource(super::User::RESOURCE_TYPE, id.to_string())
+        .into()
+}
+
 async fn get_user(
-    pool: &MySqlPool,
+    conn: &mut MySqlConnection,
     request: super::GetUserParams,
 ) -> Result<Option<super::User>, Failure> {
     let super::GetUserParams {
         id: super::UserId(id),
+        show_deleted,
     } = request;
-    let user: Option<UserRow> = sqlx::query_as(r#"SELECT * FROM `users` WHERE `id` = ?"#)
-        .bind(id)
-        .fetch_optional(pool)
-        .await
-        .context("Failed to fetch an user from DB")?;
+    let user: Option<UserRow> =
+        sqlx::query_as(r#"SELECT * FROM `users` WHERE `id` = ? AND (? OR `deleted_at` IS NULL)"#)
+            .bind(id)
+            .bind(show_deleted)
+            .fetch_optional(&mut *conn)
+            .await
+            .context("Failed to fetch an user from DB")?;
     Ok(user.map(super::User::from))
 }
 
-async fn create_user(
-    pool: &MySqlPool,
+async fn batch_get_users(
+    conn: &mut MySqlConnection,
+    request: super::BatchGetUsersParams,
+) -> Result<Vec<Option<super::User>>, Failure> {
+    let super::BatchGetUsersParams { ids } = request;
+    if ids.len() > super::BatchGetUsersParams::MAX_IDS {
+        return Err(Failure::reject_invalid_field(
+            "ids",
+            format!(
+                "At most {} ids can be requested at once",
+                super::BatchGetUsersParams::MAX_IDS
+            ),
+        ));
+    }
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut query = QueryBuilder::<MySql>::new("SELECT * FROM `users` WHERE `id` IN (");
+    let mut separated = query.separated(", ");
+    for super::UserId(id) in &ids {
+        separated.push_bind(*id);
+    }
+    query.push(")");
+    let rows: Vec<UserRow> = query
+        .build_query_as()
+        .fetch_all(&mut *conn)
+        .await
+        .context("Failed to fetch users from DB")?;
+    let found: HashMap<Uuid, super::User> = rows
+        .into_iter()
+        .map(|row| {
+            let user = super::User::from(row);
+            let user = if user.is_deleted() {
+                user.redacted()
+            } else {
+                user
+            };
+            (user.id.0, user)
+        })
+        .collect();
+    let users = ids
+        .iter()
+        .map(|super::UserId(id)| found.get(id).cloned())
+        .collect();
+    Ok(users)
+}
+
+async fn list_users(
+    conn: &mut MySqlConnection,
+    request: super::ListUsersParams,
+) -> Result<super::UserPage, Failure> {
+    let super::ListUsersParams {
+        page_size,
+        after,
+        handle_prefix,
+        show_deleted,
+    } = request;
+    let page_size = match page_size {
+        0 => super::ListUsersParams::DEFAULT_PAGE_SIZE,
+        n => n.min(super::ListUsersParams::MAX_PAGE_SIZE),
+    };
+    let mut query = QueryBuilder::<MySql>::new("SELECT * FROM `users` WHERE TRUE");
+    if !show_deleted {
+        query.push(" AND `deleted_at` IS NULL");
+    }
+    // stored handles are NFKC-normalized, see `UserHandle::new`
+    let handle_prefix = handle_prefix.map(|p| p.nfkc().collect::<String>());
+    match handle_prefix.filter(|p| !p.is_empty()) {
+        // ordering by handle lets the prefix and the cursor both seek in the handle index
+        Some(prefix) => {
+            let pattern = format!("{}%", escape_like(&prefix));
+            query.push(" AND `handle` LIKE ").push_bind(pattern);
+            if let Some(super::UserId(after)) = after {
+                let handle = cursor_handle(conn, after).await?;
+                query.push(" AND `handle` > ").push_bind(handle);
+            }
+            query.push(" ORDER BY `handle` ASC");
+        }
+        // UUIDv7 ids sort by creation time, so paging walks the primary key
+        None => {
+            if let Some(super::UserId(after)) = after {
+                query.push(" AND `id` > ").push_bind(after);
+            }
+            query.push(" ORDER BY `id` ASC");
+        }
+    }
+    // one extra row tells whether there is a next page
+    query.push(" LIMIT ").push_bind(page_size + 1);
+    let mut users: Vec<UserRow> = query
+        .build_query_as()
+        .fetch_all(&mut *conn)
+        .await
+        .context("Failed to fetch users from DB")?;
+    let next_cursor = if users.len() > page_size as usize {
+        users.truncate(page_size as usize);
+        users.last().map(|u| super::UserId(u.id))
+    } else {
+        None
+    };
+    let users = users.into_iter().map(super::User::from).collect();
+    Ok(super::UserPage { users, next_cursor })
+}
+
+/// Handle of the user a page ended on, deleted or not.
+async fn cursor_handle(conn: &mut MySqlConnection, id: Uuid) -> Result<String, Failure> {
+    let handle: Option<String> =
+        sqlx::query_scalar(r#"SELECT `handle` FROM `users` WHERE `id` = ?"#)
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await
+            .context("Failed to fetch an user from DB")?;
+    handle.ok_or_else(|| {
+        Failure::reject_failed_precondition(
+            "The user to continue after has been purged; list again from the start",
+        )
+    })
+}
+
+/// Case- and accent-insensitive, whatever the columns were created with. Available on MySQL and
+/// every MariaDB we run on, unlike the newer UCA 14 collations.
+const SEARCH_COLLATION: &str = "utf8mb4_unicode_520_ci";
+
+#[derive(Debug, Clone, FromRow)]
+struct SearchRow {
+    #[sqlx(flatten)]
+    pub user: UserRow,
+    pub match_rank: i64,
+}
+
+async fn search_users(
+    conn: &mut MySqlConnection,
+    request: super::SearchUsersParams,
+) -> Result<super::UserSearchPage, Failure> {
+    let super::SearchUsersParams {
+        query,
+        page_size,
+        after,
+    } = request;
+    let query: String = query.nfkc().collect();
+    let query = query.trim();
+    let len = query.chars().count();
+    if !(1..=super::SearchUsersParams::MAX_QUERY_CHARS).contains(&len) {
+        let max = super::SearchUsersParams::MAX_QUERY_CHARS;
+        return Err(Failure::reject_invalid_field(
+            "query",
+            format!("Query must be 1 to {max} characters"),
+        ));
+    }
+    let page_size = match page_size {
+        0 => super::ListUsersParams::DEFAULT_PAGE_SIZE,
+        n => n.min(super::ListUsersParams::MAX_PAGE_SIZE),
+    };
+    let escaped = escape_like(query);
+    let handle = format!("`handle` COLLATE {SEARCH_COLLATION}");
+    let display_name = format!("`display_name` COLLATE {SEARCH_COLLATION}");
+
+    // lower ranks are better matches; see `SearchUsersParams`
+    let mut sql = QueryBuilder::<MySql>::new("SELECT * FROM (SELECT *, CASE");
+    sql.push(format!(" WHEN {handle} = "))
+        .push_bind(query.to_owned());
+    sql.push(" THEN 0");
+    sql.push(format!(" WHEN {handle} LIKE "))
+        .push_bind(format!("{escaped}%"));
+    sql.push(" THEN 1");
+    sql.push(format!(" WHEN {display_name} LIKE "))
+        .push_bind(format!("{escaped}%"));
+    sql.push(" THEN 2");
+    sql.push(format!(" WHEN {display_name} LIKE "))
+        .push_bind(format!("% {escaped}%"));
+    sql.push(" THEN 3");
+    sql.push(" ELSE 4 END AS `match_rank` FROM `users`");
+    sql.push(format!(" WHERE `deleted_at` IS NULL AND ({handle} LIKE "))
+        .push_bind(format!("%{escaped}%"));
+    sql.push(format!(" OR {display_name} LIKE "))
+        .push_bind(format!("%{escaped}%"));
+    sql.push(")) AS `m`");
+    if let Some(super::UserSearchCursor { rank, handle }) = after {
+        sql.push(" WHERE `match_rank` > ").push_bind(rank);
+        sql.push(" OR (`match_rank` = ").push_bind(rank);
+        sql.push(" AND `handle` > ").push_bind(handle);
+        sql.push(")");
+    }
+    // one extra row tells whether there is a next page
+    sql.push(" ORDER BY `match_rank` ASC, `handle` ASC LIMIT ")
+        .push_bind(page_size + 1);
+    let mut rows: Vec<SearchRow> = sql
+        .build_query_as()
+        .fetch_all(&mut *conn)
+        .await
+        .context("Failed to search users in DB")?;
+    let next_cursor = if rows.len() > page_size as usize {
+        rows.truncate(page_size as usize);
+        rows.last()
+            .map(|row| -> Result<_, Failure> {
+                let rank = u8::try_from(row.match_rank).context("Match rank out of range")?;
+                let handle = row.user.handle.clone();
+                Ok(super::UserSearchCursor { rank, handle })
+            })
+            .transpose()?
+    } else {
+        None
+    };
+    let users = rows
+        .into_iter()
+        .map(|row| super::User::from(row.user))
+        .collect();
+    Ok(super::UserSearchPage { users, next_cursor })
+}
+
+/// Makes `value` match literally in a `LIKE` pattern.
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Takes a connection so that other domains can create an user inside their transaction.
+pub(crate) async fn create_user(
+    conn: &mut MySqlConnection,
     request: super::CreateUserParams,
 ) -> Result<super::User, Failure> {
     let id = Uuid::now_v7();
     let super::CreateUserParams {
-        name: super::UserName(name),
+        handle,
+        display_name,
     } = request;
+    let display_name = display_name.unwrap_or_else(|| handle.clone().into());
     sqlx::query(
         r#"
-        INSERT INTO `users` (`id`, `name`, `created_at`, `updated_at`)
-        VALUES (?, ?, NOW(), NOW())
+        INSERT INTO `users` (`id`, `handle`, `display_name`, `created_at`, `updated_at`)
+        VALUES (?, ?, ?, NOW(), NOW())
     "#,
     )
     .bind(id)
-    .bind(name)
-    .execute(pool)
+    .bind(String::from(handle))
+    .bind(String::from(display_name))
+    .execute(&mut *conn)
     .await
-    .context("Failed to create an user to DB")?;
+    .map_err(|e| handle_taken(e, "Failed to create an user to DB"))?;
     let user: UserRow = sqlx::query_as(r#"SELECT * FROM `users` WHERE `id` = ?"#)
         .bind(id)
-        .fetch_one(pool)
+        .fetch_one(&mut *conn)
         .await
         .context("Failed to fetch an user from DB")?;
     Ok(user.into())
 }
 
+/// Locks the row until the end of the transaction `conn` is in.
+async fn lock_user(conn: &mut MySqlConnection, id: Uuid) -> Result<Option<UserRow>, Failure> {
+    let user: Option<UserRow> =
+        sqlx::query_as(r#"SELECT * FROM `users` WHERE `id` = ? FOR UPDATE"#)
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| lock_failed(e, "Failed to fetch an user from DB"))?;
+    Ok(user)
+}
+
+/// Runs inside the transaction of `conn`, which holds the row lock until it ends.
 async fn update_user(
-    pool: &MySqlPool,
+    conn: &mut MySqlConnection,
     request: super::UpdateUserParams,
 ) -> Result<Option<super::User>, Failure> {
-    // TODO: transaction
     let super::UpdateUserParams {
         id: super::UserId(id),
-        name: super::UserName(name),
+        display_name,
+        bio,
+        avatar_url,
+        expected_version,
+    } = request;
+    let Some(user) = lock_user(conn, id)
+        .await?
+        .filter(|u| u.deleted_at.is_none())
+    else {
+        return Ok(None);
+    };
+    if expected_version.is_some_and(|v| v != user.version) {
+        return Err(version_mismatch(id));
+    }
+    // only the given columns are written
+    let mut query = QueryBuilder::<MySql>::new(
+        "UPDATE `users` SET `updated_at` = NOW(), `version` = `version` + 1",
+    );
+    let mut changed = false;
+    if let Some(display_name) = display_name {
+        query
+            .push(", `display_name` = ")
+            .push_bind(String::from(display_name));
+        changed = true;
+    }
+    if let Some(bio) = bio {
+        query.push(", `bio` = ").push_bind(String::from(bio));
+        changed = true;
+    }
+    if let Some(avatar_url) = avatar_url {
+        query
+            .push(", `avatar_url` = ")
+            .push_bind(avatar_url.map(String::from));
+        changed = true;
+    }
+    if !changed {
+        return Ok(Some(user.into()));
+    }
+    query.push(" WHERE `id` = ").push_bind(id);
+    query
+        .build()
+        .execute(&mut *conn)
+        .await
+        .context("Failed to update an user in DB")?;
+    fetch_user(conn, id).await.map(Some)
+}
+
+/// Runs inside the transaction of `conn`, which holds the row lock until it ends. Also returns
+/// the message events to publish once it is committed.
+async fn delete_user(
+    conn: &mut MySqlConnection,
+    policy: super::DeletionPolicy,
+    request: super::DeleteUserParams,
+) -> Result<Option<(super::UserDeletion, Vec<MessageEvent>)>, Failure> {
+    let super::DeleteUserParams {
+        id: super::UserId(id),
+        expected_version,
     } = request;
+    let Some(user) = lock_user(conn, id)
+        .await?
+        .filter(|u| u.deleted_at.is_none())
+    else {
+        return Ok(None);
+    };
+    if expected_version.is_some_and(|v| v != user.version) {
+        return Err(version_mismatch(id));
+    }
     sqlx::query(
         r#"
         UPDATE `users`
-        SET `name` = ?, `updated_at` = NOW()
+        SET `deleted_at` = NOW(), `updated_at` = NOW(), `version` = `version` + 1
         WHERE `id` = ?
     "#,
     )
-    .bind(name)
     .bind(id)
-    .execute(pool)
-    .await
-    .context("Failed to update an user in DB")?;
-    get_user(
-        pool,
-        super::GetUserParams {
-            id: super::UserId(id),
-        },
-    )
+    .execute(&mut *conn)
     .await
+    .context("Failed to delete an user in DB")?;
+    let (sessions_revoked, api_keys_revoked) =
+        crate::auth::revoke_user_credentials(conn, id).await?;
+    let crate::channel::ChannelRemoval {
+        memberships_removed,
+        channels_transferred,
+        channels_archived,
+    } = crate::channel::remove_user_from_channels(conn, id).await?;
+    let events: Vec<_> = match policy.messages {
+        super::MessagePolicy::Keep => vec![],
+        super::MessagePolicy::Anonymize => crate::message::anonymize_user_messages(conn, id)
+            .await?
+            .into_iter()
+            .map(MessageEvent::Updated)
+            .collect(),
+        super::MessagePolicy::Delete => crate::message::delete_user_messages(conn, id)
+            .await?
+            .into_iter()
+            .map(MessageEvent::Deleted)
+            .collect(),
+    };
+    let (messages_anonymized, messages_deleted) = match policy.messages {
+        super::MessagePolicy::Keep => (0, 0),
+        super::MessagePolicy::Anonymize => (events.len() as u64, 0),
+        super::MessagePolicy::Delete => (0, events.len() as u64),
+    };
+    let report = super::DeletionReport {
+        sessions_revoked,
+        api_keys_revoked,
+        memberships_removed,
+        channels_transferred,
+        channels_archived,
+        messages_anonymized,
+        messages_deleted,
+    };
+    let user = fetch_user(conn, id).await?;
+    tracing::info!(user_id = %id, ?report, "Deleted an user");
+    Ok(Some((super::UserDeletion { user, report }, events)))
 }
 
-async fn delete_user(
-    pool: &MySqlPool,
-    request: super::DeleteUserParams,
+async fn restore_user(
+    conn: &mut MySqlConnection,
+    request: super::RestoreUserParams,
 ) -> Result<Option<super::User>, Failure> {
-    // TODO: transaction
-    let super::DeleteUserParams {
+    let super::RestoreUserParams {
         id: super::UserId(id),
     } = request;
-    let get_request = super::GetUserParams {
-        id: super::UserId(id),
-    };
-    let Some(user) = get_user(pool, get_request).await? else {
+    let Some(user) = lock_user(conn, id).await? else {
         return Ok(None);
     };
-    sqlx::query(r#"DELETE FROM `users` WHERE `id` = ?"#)
+    if user.deleted_at.is_none() {
+        return Err(Reject::failed_precondition("User is not deleted")
+            .with_resource(super::User::RESOURCE_TYPE, id.to_string())
+            .into());
+    }
+    sqlx::query(
+        r#"
+        UPDATE `users`
+        SET `deleted_at` = NULL, `updated_at` = NOW(), `version` = `version` + 1
+        WHERE `id` = ?
+    "#,
+    )
+    .bind(id)
+    .execute(&mut *conn)
+    .await
+    .context("Failed to restore an user in DB")?;
+    fetch_user(conn, id).await.map(Some)
+}
+
+async fn fetch_user(conn: &mut MySqlConnection, id: Uuid) -> Result<super::User, Failure> {
+    let user: UserRow = sqlx::query_as(r#"SELECT * FROM `users` WHERE `id` = ?"#)
         .bind(id)
-        .execute(pool)
+        .fetch_one(&mut *conn)
         .await
-        .context("Failed to delete an user from DB")?;
-    Ok(Some(user))
+        .context("Failed to fetch an user from DB")?;
+    Ok(user.into())
+}
+
+// MARK: purge
+
+/// Hard-deletes up to `limit` users deleted more than `retention` ago. Returns how many were
+/// purged.
+async fn purge_deleted_users(
+    pool: &MySqlPool,
+    hub: &MessageHub,
+    retention: std::time::Duration,
+    limit: u32,
+) -> Result<u64, Failure> {
+    let mut tx = Tx::begin(pool).await?;
+    let ids: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+        SELECT `id` FROM `users`
+        WHERE `deleted_at` < NOW() - INTERVAL ? SECOND
+        ORDER BY `deleted_at` ASC
+        LIMIT ?
+        FOR UPDATE
+    "#,
+    )
+    .bind(retention.as_secs())
+    .bind(limit)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| lock_failed(e, "Failed to fetch deleted users from DB"))?;
+    let mut anonymized = vec![];
+    for &id in &ids {
+        // messages kept under `MessagePolicy::Keep` must not point at a missing user
+        anonymized.extend(crate::message::anonymize_user_messages(&mut tx, id).await?);
+        crate::auth::delete_user_credentials(&mut tx, id).await?;
+        crate::channel::delete_user_memberships(&mut tx, id).await?;
+        sqlx::query(r#"DELETE FROM `users` WHERE `id` = ?"#)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to purge an user from DB")?;
+    }
+    tx.commit().await?;
+    for message in anonymized {
+        hub.publish(MessageEvent::Updated(message));
+    }
+    Ok(ids.len() as u64)
+}
+
+/// Purges users deleted more than `retention` ago, checking every `interval` until `shutdown`
+/// is cancelled.
+pub fn spawn_purge_task(
+    pool: MySqlPool,
+    hub: MessageHub,
+    retention: std::time::Duration,
+    interval: std::time::Duration,
+    shutdown: CancellationToken,
+) -> tokio::task::JoinHandle<()> {
+    const BATCH: u32 = 100;
+
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                () = shutdown.cancelled() => break,
+                _ = ticks.tick() => {}
+            }
+            // a full batch means there may be more
+            loop {
+                match purge_deleted_users(&pool, &hub, retention, BATCH).await {
+                    Ok(purged) => {
+                        if purged > 0 {
+                            tracing::info!(purged, "Purged deleted users");
+                        }
+                        if purged < u64::from(BATCH) {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!(error = ?e, "Failed to purge deleted users");
+                        break;
+                    }
+                }
+            }
+        }
+    })
 }
 
 // MARK: impl UserService
 
 impl<Ctx> super::UserService<Ctx> for Impl
 where
-    Ctx: AsRef<MySqlPool> + Send + Sync,
+    Ctx: Database + AsRef<MessageHub> + AsRef<RevocationList> + Send + Sync,
 {
     async fn get_user<'a>(
         &'a self,
         ctx: &'a Ctx,
         request: super::GetUserParams,
     ) -> Result<super::User, Failure> {
-        get_user(ctx.as_ref(), request)
+        let id = request.id;
+        let mut conn = ctx.conn().await?;
+        get_user(&mut conn, request)
             .await?
-            .ok_or_else(|| Failure::reject_not_found("User not found"))
+            .ok_or_else(|| user_not_found(id))
+    }
+
+    async fn batch_get_users<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::BatchGetUsersParams,
+    ) -> Result<Vec<Option<super::User>>, Failure> {
+        let mut conn = ctx.conn().await?;
+        batch_get_users(&mut conn, request).await
+    }
+
+    async fn list_users<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::ListUsersParams,
+    ) -> Result<super::UserPage, Failure> {
+        let mut conn = ctx.conn().await?;
+        list_users(&mut conn, request).await
+    }
+
+    async fn search_users<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::SearchUsersParams,
+    ) -> Result<super::UserSearchPage, Failure> {
+        let mut conn = ctx.conn().await?;
+        search_users(&mut conn, request).await
     }
 
     async fn create_user<'a>(
@@ -146,7 +651,8 @@ where
         ctx: &'a Ctx,
         request: super::CreateUserParams,
     ) -> Result<super::User, Failure> {
-        create_user(ctx.as_ref(), request).await
+        let mut conn = ctx.conn().await?;
+        create_user(&mut conn, request).await
     }
 
     async fn update_user<'a>(
@@ -154,18 +660,64 @@ where
         ctx: &'a Ctx,
         request: super::UpdateUserParams,
     ) -> Result<super::User, Failure> {
-        update_user(ctx.as_ref(), request)
+        let id = request.id;
+        let mut tx = ctx.begin().await?;
+        let user = update_user(&mut tx, request)
             .await?
-            .ok_or_else(|| Failure::reject_not_found("User not found"))
+            .ok_or_else(|| user_not_found(id))?;
+        tx.commit().await?;
+        Ok(user)
     }
 
     async fn delete_user<'a>(
         &'a self,
         ctx: &'a Ctx,
         request: super::DeleteUserParams,
+    ) -> Result<super::UserDeletion, Failure> {
+        let id = request.id;
+        let mut tx = ctx.begin().await?;
+        let (deletion, events) = delete_user(&mut tx, self.deletion_policy, request)
+            .await?
+            .ok_or_else(|| user_not_found(id))?;
+        tx.commit().await?;
+        let revocations: &RevocationList = ctx.as_ref();
+        let revocations = revocations.clone();
+        let hub: &MessageHub = ctx.as_ref();
+        let hub = hub.clone();
+        ctx.after_commit(move || {
+            revocations.revoke_user(id);
+            hub.disconnect_user(id);
+            for event in events {
+                hub.publish(event);
+            }
+        });
+        Ok(deletion)
+    }
+
+    async fn restore_user<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        request: super::RestoreUserParams,
     ) -> Result<super::User, Failure> {
-        delete_user(ctx.as_ref(), request)
+        let id = request.id;
+        let mut tx = ctx.begin().await?;
+        let user = restore_user(&mut tx, request)
             .await?
-            .ok_or_else(|| Failure::reject_not_found("User not found"))
+            .ok_or_else(|| user_not_found(id))?;
+        tx.commit().await?;
+        Ok(user)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_like_escapes_wildcards_and_the_escape_character() {
+        assert_eq!(escape_like("alice"), "alice");
+        assert_eq!(escape_like("a_b%c"), r"a\_b\%c");
+        assert_eq!(escape_like(r"a\b"), r"a\\b");
+        assert_eq!(escape_like(""), "");
     }
 }